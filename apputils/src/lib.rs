@@ -1,5 +1,7 @@
+use rattlesnake::{Field, Genome};
 use simplelog::WriteLogger;
-use std::{env, fs};
+use std::path::Path;
+use std::{env, fs, io, process};
 
 pub fn enable_logging(log_dir: &str, log_file: &str) {
     if env::var("RS_LOG").unwrap_or("false".to_string()) == "true" {
@@ -14,3 +16,65 @@ pub fn enable_logging(log_dir: &str, log_file: &str) {
         .unwrap();
     }
 }
+
+// Value following `name` in the command line arguments.
+pub fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
+}
+
+// Values following every occurrence of `name` in the command line arguments.
+pub fn option_values<'a>(args: &'a [String], name: &str) -> Vec<&'a str> {
+    args.windows(2)
+        .filter(|pair| pair[0] == name)
+        .map(|pair| pair[1].as_str())
+        .collect()
+}
+
+// Number following `name` in the command line arguments, or `default`.
+pub fn parse_number<T: std::str::FromStr>(
+    args: &[String],
+    name: &str,
+    default: T,
+) -> T {
+    option_value(args, name).map_or(default, |value| {
+        value.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number {value} for {name}");
+            process::exit(1);
+        })
+    })
+}
+
+// Load a level or exit with an error message.
+pub fn load_level(path: &str) -> Field {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Failed to read level {path}: {err}");
+        process::exit(1);
+    });
+    Field::from_level(&text).unwrap_or_else(|err| {
+        eprintln!("Invalid level {path}: {err}");
+        process::exit(1);
+    })
+}
+
+// Load an evolved genome or exit with an error message.
+pub fn load_genome(path: &str) -> Genome {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Failed to read genome {path}: {err}");
+        process::exit(1);
+    });
+    Genome::from_json(&text).unwrap_or_else(|err| {
+        eprintln!("Invalid genome {path}: {err}");
+        process::exit(1);
+    })
+}
+
+// Write `contents` to `path`, creating missing parent directories.
+pub fn write_file(path: &str, contents: &str) -> io::Result<()> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
@@ -0,0 +1,58 @@
+#!/usr/bin/env python3
+"""Example bot for the line-delimited JSON protocol.
+
+Heads for the nearest food and avoids walls and bodies, see
+rattlesnake/src/external.rs for the protocol.
+"""
+import json
+import sys
+
+MOVES = {"up": (0, -1), "down": (0, 1), "left": (-1, 0), "right": (1, 0)}
+
+
+def choose(request):
+    field = request["field"]
+    snake = request["snakes"][request["you"]]
+    head = snake["body"][0]
+    blocked = {tuple(pos) for pos in field["walls"]}
+    for other in request["snakes"]:
+        if other["alive"]:
+            blocked.update(tuple(pos) for pos in other["body"][:-1])
+    width = field["x_max"] - field["x_min"] + 1
+    height = field["y_max"] - field["y_min"] + 1
+
+    def target(delta):
+        x, y = head[0] + delta[0], head[1] + delta[1]
+        if field["wrap"]:
+            x = field["x_min"] + (x - field["x_min"]) % width
+            y = field["y_min"] + (y - field["y_min"]) % height
+        return x, y
+
+    def free(pos):
+        x, y = pos
+        inside = field["x_min"] <= x <= field["x_max"] and (
+            field["y_min"] <= y <= field["y_max"]
+        )
+        return inside and pos not in blocked
+
+    def distance(pos):
+        food = request["food"]
+        return min((abs(pos[0] - f[0]) + abs(pos[1] - f[1]) for f in food),
+                   default=0)
+
+    # Reversing is not possible, the snake keeps going instead
+    back = tuple(-d for d in snake["direction"])
+    options = [
+        name
+        for name, delta in MOVES.items()
+        if delta != back and free(target(delta))
+    ]
+    if not options:
+        return "idle"
+    return min(options, key=lambda name: distance(target(MOVES[name])))
+
+
+for line in sys.stdin:
+    request = json.loads(line)
+    reply = {"tick": request["tick"], "move": choose(request)}
+    print(json.dumps(reply), flush=True)
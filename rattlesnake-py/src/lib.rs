@@ -0,0 +1,306 @@
+//! Python bindings for the snake engine.
+//!
+//! Build with `maturin develop` in this directory. Doc comments on the
+//! exported items become their Python docstrings.
+use numpy::{PyArray1, PyArray3, PyArrayMethods};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rattlesnake::{
+    Action, Boundary, CHANNELS, Env, Field, GameResult, GameState, Observation,
+    Outcome, PlayerEvent, Rewards, SeededRng, play,
+};
+
+/// The playing field: size, boundary and walls.
+#[pyclass(name = "Field", frozen)]
+#[derive(Clone)]
+struct PyField(Field);
+
+#[pymethods]
+impl PyField {
+    #[new]
+    #[pyo3(signature = (width, height, wrap = false))]
+    fn new(width: u16, height: u16, wrap: bool) -> Self {
+        PyField(Field::new(width, height).with_boundary(boundary(wrap)))
+    }
+
+    /// Create a field from a text-based level.
+    #[staticmethod]
+    #[pyo3(signature = (text, wrap = false))]
+    fn from_level(text: &str, wrap: bool) -> PyResult<Self> {
+        let field = Field::from_level(text)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(PyField(field.with_boundary(boundary(wrap))))
+    }
+
+    #[getter]
+    fn width(&self) -> u16 {
+        self.0.width()
+    }
+
+    #[getter]
+    fn height(&self) -> u16 {
+        self.0.height()
+    }
+
+    #[getter]
+    fn wrap(&self) -> bool {
+        self.0.boundary() == Boundary::Wrap
+    }
+
+    #[getter]
+    fn walls(&self) -> Vec<(u16, u16)> {
+        self.0.walls().copied().collect()
+    }
+}
+
+fn boundary(wrap: bool) -> Boundary {
+    if wrap {
+        Boundary::Wrap
+    } else {
+        Boundary::Walls
+    }
+}
+
+/// An event passed to `Game.step` for one snake.
+#[pyclass(name = "PlayerEvent", eq, eq_int)]
+#[derive(Clone, Copy, PartialEq)]
+enum PyPlayerEvent {
+    Up,
+    Down,
+    Left,
+    Right,
+    TurnLeft,
+    TurnRight,
+    Idle,
+    Quit,
+}
+
+impl From<PyPlayerEvent> for PlayerEvent {
+    fn from(event: PyPlayerEvent) -> Self {
+        match event {
+            PyPlayerEvent::Up => PlayerEvent::Up,
+            PyPlayerEvent::Down => PlayerEvent::Down,
+            PyPlayerEvent::Left => PlayerEvent::Left,
+            PyPlayerEvent::Right => PlayerEvent::Right,
+            PyPlayerEvent::TurnLeft => PlayerEvent::TurnLeft,
+            PyPlayerEvent::TurnRight => PlayerEvent::TurnRight,
+            PyPlayerEvent::Idle => PlayerEvent::Idle,
+            PyPlayerEvent::Quit => PlayerEvent::Quit,
+        }
+    }
+}
+
+/// A snapshot of a game: snakes and food. Positions are `(x, y)` tuples.
+#[pyclass(name = "GameState", frozen)]
+struct PyGameState(GameState);
+
+#[pymethods]
+impl PyGameState {
+    #[getter]
+    fn food(&self) -> Vec<(u16, u16)> {
+        self.0.food.iter().map(|food| food.pos).collect()
+    }
+
+    /// Kind of each item of `food`, e.g. `"bonus"`.
+    #[getter]
+    fn food_kinds(&self) -> Vec<&'static str> {
+        self.0.food.iter().map(|food| food.kind.name()).collect()
+    }
+
+    /// Power-ups in effect as `(snake, power, ticks left)`, e.g.
+    /// `(0, "ghost", 12)`.
+    #[getter]
+    fn effects(&self) -> Vec<(usize, &'static str, u32)> {
+        let effects = self.0.effects.iter();
+        effects
+            .map(|effect| (effect.snake, effect.power.name(), effect.ticks))
+            .collect()
+    }
+
+    /// Cells of each snake, head first.
+    #[getter]
+    fn bodies(&self) -> Vec<Vec<(u16, u16)>> {
+        let snakes = self.0.snakes.iter();
+        snakes
+            .map(|snake| snake.body.iter().copied().collect())
+            .collect()
+    }
+
+    #[getter]
+    fn scores(&self) -> Vec<u8> {
+        self.0.snakes.iter().map(|snake| snake.score).collect()
+    }
+
+    #[getter]
+    fn alive(&self) -> Vec<bool> {
+        self.0.snakes.iter().map(|snake| snake.alive).collect()
+    }
+}
+
+/// A game of one or more snakes, driven one tick at a time.
+#[pyclass(name = "Game")]
+struct PyGame {
+    field: Field,
+    state: GameState,
+    rng: SeededRng,
+    outcome: Option<Outcome>,
+}
+
+#[pymethods]
+impl PyGame {
+    #[new]
+    #[pyo3(signature = (field, players = 1, seed = 0))]
+    fn new(field: &PyField, players: usize, seed: u64) -> Self {
+        PyGame {
+            field: field.0.clone(),
+            state: GameState::with_players(players),
+            rng: SeededRng::new(seed),
+            outcome: None,
+        }
+    }
+
+    /// Play one tick with an event per snake. Returns how the game ended,
+    /// or `None` while it goes on.
+    fn step(&mut self, events: Vec<PyPlayerEvent>) -> PyResult<Option<String>> {
+        if self.outcome.is_some() {
+            return Err(PyValueError::new_err("the game is over"));
+        }
+        let events: Vec<PlayerEvent> =
+            events.into_iter().map(PlayerEvent::from).collect();
+        let result = play(&mut self.state, &self.field, &events, &mut self.rng);
+        if let GameResult::GameOver(outcome) = result {
+            self.outcome = Some(outcome);
+        }
+        Ok(self.outcome())
+    }
+
+    /// The game as an array of shape `(CHANNELS, height, width)`.
+    fn observation<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<Bound<'py, PyArray3<f32>>> {
+        to_array(py, Observation::new(&self.field, &self.state))
+    }
+
+    #[getter]
+    fn state(&self) -> PyGameState {
+        PyGameState(self.state.clone())
+    }
+
+    #[getter]
+    fn outcome(&self) -> Option<String> {
+        self.outcome.map(|outcome| outcome.to_string())
+    }
+}
+
+/// A Gym-style single-player environment. Actions are 0 to 3 for up, down,
+/// left and right, or with `relative=True` 0 to 2 for straight on, turning
+/// left and turning right; observations are arrays of shape
+/// `(CHANNELS, height, width)` with channels body, head, food and walls.
+#[pyclass(name = "Env")]
+struct PyEnv {
+    env: Env,
+    relative: bool,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (
+        field,
+        *,
+        step_limit = None,
+        relative = false,
+        food = 1.0,
+        death = -1.0,
+        win = 1.0,
+        step = 0.0,
+        approach = 0.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        field: &PyField,
+        step_limit: Option<usize>,
+        relative: bool,
+        food: f32,
+        death: f32,
+        win: f32,
+        step: f32,
+        approach: f32,
+    ) -> Self {
+        let rewards = Rewards {
+            food,
+            death,
+            win,
+            step,
+            approach,
+        };
+        let mut env = Env::new(field.0.clone()).with_rewards(rewards);
+        if let Some(steps) = step_limit {
+            env = env.with_step_limit(steps);
+        }
+        PyEnv { env, relative }
+    }
+
+    /// Start a new episode and return its first observation.
+    #[pyo3(signature = (seed = 0))]
+    fn reset<'py>(
+        &mut self,
+        py: Python<'py>,
+        seed: u64,
+    ) -> PyResult<Bound<'py, PyArray3<f32>>> {
+        to_array(py, self.env.reset(seed))
+    }
+
+    /// Take an action; returns `(observation, reward, done, info)`.
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: usize,
+    ) -> PyResult<(Bound<'py, PyArray3<f32>>, f32, bool, Bound<'py, PyDict>)>
+    {
+        let parsed = if self.relative {
+            Action::from_relative_index(action)
+        } else {
+            Action::from_index(action)
+        };
+        let action = parsed.ok_or_else(|| {
+            PyValueError::new_err(format!("invalid action {action}"))
+        })?;
+        let (observation, reward, done, info) = self.env.step(action);
+        let dict = PyDict::new(py);
+        dict.set_item("score", info.score)?;
+        dict.set_item("length", info.length)?;
+        dict.set_item("steps", info.steps)?;
+        dict.set_item("outcome", info.outcome.map(|o| o.to_string()))?;
+        dict.set_item("truncated", info.truncated)?;
+        Ok((to_array(py, observation)?, reward, done, dict))
+    }
+
+    #[getter]
+    fn state(&self) -> PyGameState {
+        PyGameState(self.env.state().clone())
+    }
+}
+
+fn to_array(
+    py: Python<'_>,
+    observation: Observation,
+) -> PyResult<Bound<'_, PyArray3<f32>>> {
+    let shape = [CHANNELS, observation.height, observation.width];
+    PyArray1::from_vec(py, observation.data).reshape(shape)
+}
+
+#[pymodule]
+#[pyo3(name = "rattlesnake")]
+fn rattlesnake_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("CHANNELS", CHANNELS)?;
+    m.add_class::<PyField>()?;
+    m.add_class::<PyPlayerEvent>()?;
+    m.add_class::<PyGameState>()?;
+    m.add_class::<PyGame>()?;
+    m.add_class::<PyEnv>()?;
+    Ok(())
+}
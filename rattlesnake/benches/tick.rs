@@ -0,0 +1,70 @@
+//! Time per tick for long snakes on a large field.
+//!
+//! Run with `cargo bench -p rattlesnake`. A tick should take about the same
+//! time whatever the length of the snake.
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rattlesnake::{
+    Boundary, Field, Food, GameResult, GameState, PlayerEvent, SeededRng, play,
+};
+use std::hint::black_box;
+
+const SIZE: u16 = 1024;
+const TICKS: usize = 100;
+
+// A snake of `len` segments with its head at (1, 1), heading right along an
+// empty row, and its body winding through the rows below.
+fn long_snake(field: &Field, len: usize) -> (GameState, SeededRng) {
+    let mut state = GameState::new();
+    let snake = &mut state.snakes[0];
+    snake.body.push_back((1, 1));
+    for i in 0..len - 1 {
+        let row = (i / SIZE as usize) as u16;
+        let col = (i % SIZE as usize) as u16;
+        let x = if row.is_multiple_of(2) {
+            1 + col
+        } else {
+            SIZE - col
+        };
+        snake.body.push_back((x, 2 + row));
+    }
+    snake.direction = (1, 0);
+    state.food.push(Food::new((SIZE, 1)));
+
+    // The first tick indexes the field, keep it out of the measurement.
+    let mut rng = SeededRng::new(0);
+    let result = play(&mut state, field, &[PlayerEvent::Idle], &mut rng);
+    assert_eq!(result, GameResult::Continue);
+    (state, rng)
+}
+
+fn step(state: &mut GameState, field: &Field, rng: &mut SeededRng) {
+    black_box(play(state, field, &[PlayerEvent::Idle], rng));
+}
+
+fn tick(c: &mut Criterion) {
+    let field = Field::new(SIZE, SIZE).with_boundary(Boundary::Wrap);
+    let mut group = c.benchmark_group("tick");
+    for len in [100, 10_000, 100_000] {
+        let (state, rng) = long_snake(&field, len);
+        group.bench_function(format!("snake of {len}"), |b| {
+            b.iter_batched_ref(
+                || {
+                    // Fault in the fresh copy of the state before timing.
+                    let (mut state, mut rng) = (state.clone(), rng.clone());
+                    step(&mut state, &field, &mut rng);
+                    (state, rng)
+                },
+                |(state, rng)| {
+                    for _ in 0..TICKS {
+                        step(state, &field, rng);
+                    }
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tick);
+criterion_main!(benches);
@@ -0,0 +1,288 @@
+//! The Battlesnake HTTP API, see https://docs.battlesnake.com/api.
+//!
+//! Battlesnake boards start at `(0, 0)` in the bottom left corner, with `y`
+//! growing upwards, whereas fields start at `(x_min, y_min)` in the top left
+//! corner. Walls are sent as hazards, and hazards are read back as walls.
+//! There is no starvation, so every snake has full health.
+use crate::{Boundary, Field, Food, GameState, PlayerEvent, Snake};
+use serde::{Deserialize, Serialize};
+
+pub const API_VERSION: &str = "1";
+// Health of every snake, as the engine has no starvation.
+pub const HEALTH: u32 = 100;
+
+// Answer to `GET /`, describing the snake.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnakeInfo {
+    pub apiversion: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(default)]
+    pub head: String,
+    #[serde(default)]
+    pub tail: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+// Body of the `/start`, `/move` and `/end` requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRequest {
+    pub game: Game,
+    pub turn: u64,
+    pub board: Board,
+    pub you: Battlesnake,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Game {
+    pub id: String,
+    pub ruleset: Ruleset,
+    #[serde(default)]
+    pub map: String,
+    // Time to answer a request, in milliseconds.
+    pub timeout: u64,
+    #[serde(default)]
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ruleset {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Board {
+    pub height: u16,
+    pub width: u16,
+    pub food: Vec<Coord>,
+    #[serde(default)]
+    pub hazards: Vec<Coord>,
+    pub snakes: Vec<Battlesnake>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Battlesnake {
+    pub id: String,
+    pub name: String,
+    pub health: u32,
+    pub body: Vec<Coord>,
+    #[serde(default)]
+    pub latency: String,
+    pub head: Coord,
+    pub length: usize,
+    #[serde(default)]
+    pub shout: String,
+}
+
+#[derive(
+    Debug, Default, PartialEq, Eq, Copy, Clone, Serialize, Deserialize,
+)]
+pub struct Coord {
+    pub x: u16,
+    pub y: u16,
+}
+
+// Answer to `/move`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveResponse {
+    #[serde(rename = "move")]
+    pub direction: Move,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shout: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl From<Move> for PlayerEvent {
+    fn from(direction: Move) -> Self {
+        match direction {
+            Move::Up => PlayerEvent::Up,
+            Move::Down => PlayerEvent::Down,
+            Move::Left => PlayerEvent::Left,
+            Move::Right => PlayerEvent::Right,
+        }
+    }
+}
+
+impl Move {
+    // The move of `event`, or `None` for events that are not a move.
+    pub fn from_event(event: PlayerEvent) -> Option<Self> {
+        match event {
+            PlayerEvent::Up => Some(Move::Up),
+            PlayerEvent::Down => Some(Move::Down),
+            PlayerEvent::Left => Some(Move::Left),
+            PlayerEvent::Right => Some(Move::Right),
+            _ => None,
+        }
+    }
+}
+
+// Id of snake `snake` in the requests of `GameRequest::new`.
+pub fn snake_id(snake: usize) -> String {
+    format!("snake-{}", snake + 1)
+}
+
+impl GameRequest {
+    // Describe `state` to snake `you` on turn `turn` of game `id`, in which
+    // snakes get `timeout` milliseconds to answer.
+    //
+    // Dead snakes are left out of the board, and so is food that a body
+    // still covers. Cells off the field, such as the head of a snake that
+    // ran into the edge, are dropped.
+    pub fn new(
+        id: &str,
+        state: &GameState,
+        field: &Field,
+        turn: u64,
+        you: usize,
+        timeout: u64,
+    ) -> Self {
+        let coord = |pos: &(u16, u16)| {
+            field.contains(*pos).then(|| Coord {
+                x: pos.0 - field.x_min,
+                y: field.y_max - pos.1,
+            })
+        };
+        let battlesnake = |i: usize, snake: &Snake| {
+            let body: Vec<Coord> =
+                snake.body.iter().filter_map(coord).collect();
+            Battlesnake {
+                id: snake_id(i),
+                name: format!("Snake {}", i + 1),
+                health: HEALTH,
+                head: body.first().copied().unwrap_or_default(),
+                body,
+                latency: String::new(),
+                length: snake.body.len(),
+                shout: String::new(),
+            }
+        };
+        let ruleset = match field.boundary() {
+            Boundary::Walls => "standard",
+            Boundary::Wrap => "wrapped",
+        };
+        GameRequest {
+            game: Game {
+                id: id.to_string(),
+                ruleset: Ruleset {
+                    name: ruleset.to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                map: "standard".to_string(),
+                timeout,
+                source: "custom".to_string(),
+            },
+            turn,
+            board: Board {
+                height: field.height(),
+                width: field.width(),
+                food: state
+                    .food
+                    .iter()
+                    .map(|food| food.pos)
+                    .filter(|pos| {
+                        !state.alive().any(|snake| snake.body.contains(pos))
+                    })
+                    .filter_map(|pos| coord(&pos))
+                    .collect(),
+                hazards: field.walls().filter_map(coord).collect(),
+                snakes: state
+                    .snakes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, snake)| snake.alive)
+                    .map(|(i, snake)| battlesnake(i, snake))
+                    .collect(),
+            },
+            you: battlesnake(you, &state.snakes[you]),
+        }
+    }
+
+    // Rebuild the field and state the request describes, with the index of
+    // the requesting snake. `None` if `you` is not on the board.
+    pub fn to_game(&self) -> Option<(Field, GameState, usize)> {
+        let board = &self.board;
+        let boundary = match self.game.ruleset.name.as_str() {
+            "wrapped" => Boundary::Wrap,
+            _ => Boundary::Walls,
+        };
+        let mut field =
+            Field::new(board.width, board.height).with_boundary(boundary);
+        // Coordinates off the board are dropped
+        let pos = |coord: &Coord| {
+            (coord.x < board.width && coord.y < board.height)
+                .then(|| (field.x_min + coord.x, field.y_max - coord.y))
+        };
+        let walls = board.hazards.iter().filter_map(pos).collect();
+        let mut state = GameState::with_players(0);
+        state.food = board.food.iter().filter_map(pos).map(Food::new).collect();
+        for snake in &board.snakes {
+            let body: Vec<_> = snake.body.iter().filter_map(pos).collect();
+            let direction = match body.as_slice() {
+                [head, neck, ..] => (
+                    step(neck.0, head.0, field.width()),
+                    step(neck.1, head.1, field.height()),
+                ),
+                _ => (0, 0),
+            };
+            state.snakes.push(Snake {
+                body: body.into(),
+                direction,
+                score: snake.length.saturating_sub(1).min(255) as u8,
+                alive: true,
+                death: None,
+            });
+        }
+        field.walls = walls;
+        let you = board.snakes.iter().position(|s| s.id == self.you.id)?;
+        Some((field, state, you))
+    }
+}
+
+// Direction of a step from `from` to `to` along an axis of length `len`,
+// which crosses the border when wrapping.
+fn step(from: u16, to: u16, len: u16) -> i16 {
+    let delta = to as i16 - from as i16;
+    if delta.abs() > 1 && delta.unsigned_abs() == len - 1 {
+        -delta.signum()
+    } else {
+        delta.signum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SeededRng, play};
+
+    #[test]
+    fn cells_off_the_field_are_dropped() {
+        let field = Field::new(5, 5);
+        let mut state = GameState::with_players(2);
+        let mut rng = SeededRng::new(0);
+        play(&mut state, &field, &[PlayerEvent::Idle; 2], &mut rng);
+        // Snake 1 ran into the left edge, its head is off the field
+        let dead = &mut state.snakes[0];
+        dead.body = [(0, 3), (1, 3), (2, 3)].into_iter().collect();
+        dead.alive = false;
+
+        let request = GameRequest::new("test", &state, &field, 3, 0, 500);
+        assert_eq!(request.board.snakes.len(), 1);
+        let you = &request.you;
+        assert_eq!(you.body, [Coord { x: 0, y: 2 }, Coord { x: 1, y: 2 }]);
+        assert_eq!(you.head, Coord { x: 0, y: 2 });
+        assert_eq!(you.length, 3);
+    }
+}
@@ -0,0 +1,668 @@
+//! Built-in computer players.
+//!
+//! Bots look at the field as it will be at the start of the next tick: bodies
+//! block, except for tails that are about to move on.
+use crate::{
+    Controller, Field, Food, GameResult, GameState, Outcome, PlayerEvent,
+    Random, SeededRng, Snake, play,
+};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MOVES: [(PlayerEvent, (i16, i16)); 4] = [
+    (PlayerEvent::Up, (0, -1)),
+    (PlayerEvent::Down, (0, 1)),
+    (PlayerEvent::Left, (-1, 0)),
+    (PlayerEvent::Right, (1, 0)),
+];
+
+// Heads straight for the nearest food, only avoiding certain death this tick.
+#[derive(Debug, Default, Clone)]
+pub struct Greedy;
+
+impl Controller for Greedy {
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent {
+        let snake = &state.snakes[snake];
+        let Some(head) = snake.head().filter(|_| snake.alive) else {
+            return PlayerEvent::Idle;
+        };
+        let board = Board::new(state, field);
+        board
+            .moves(head, snake.direction)
+            .into_iter()
+            .min_by_key(|(_, pos)| board.food_distance(*pos))
+            .map_or(PlayerEvent::Idle, |(event, _)| event)
+    }
+}
+
+// Follows the shortest path to food, as long as it can still reach its own
+// tail once there. Otherwise it chases its tail, which keeps a way out open.
+#[derive(Debug, Default, Clone)]
+pub struct Pathfinder;
+
+impl Controller for Pathfinder {
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent {
+        let snake = &state.snakes[snake];
+        let Some(head) = snake.head().filter(|_| snake.alive) else {
+            return PlayerEvent::Idle;
+        };
+        let board = Board::new(state, field);
+        let moves = board.moves(head, snake.direction);
+        let event_to =
+            |pos| moves.iter().find(|(_, next)| *next == pos).map(|(e, _)| *e);
+
+        if let Some(path) =
+            board.path(&board.blocked, head, snake.direction, |pos| {
+                board.targets.contains(&pos)
+            })
+            && board.can_reach_tail(snake, &path, true)
+            && let Some(event) = event_to(path[0])
+        {
+            return event;
+        }
+
+        // Chase the tail, preferring the move that leaves the most room
+        let room = |pos: (u16, u16)| board.reachable(&board.blocked, pos);
+        let safe = moves
+            .iter()
+            .filter(|(_, pos)| board.can_reach_tail(snake, &[*pos], false))
+            .max_by_key(|(_, pos)| room(*pos));
+        safe.or_else(|| moves.iter().max_by_key(|(_, pos)| room(*pos)))
+            .map_or(PlayerEvent::Idle, |(event, _)| *event)
+    }
+}
+
+// Follows a cycle through every cell of the field, which fills any empty
+// field without ever getting stuck. Falls back to `Pathfinder` on fields
+// without such a cycle, i.e. with walls inside or an odd number of cells.
+#[derive(Debug, Default, Clone)]
+pub struct Hamiltonian {
+    // Field `next` was built for, as fields of the same size may differ in
+    // their walls.
+    field: Option<Field>,
+    // Cell following each cell on the cycle, by row-major index.
+    next: Option<Vec<(u16, u16)>>,
+}
+
+impl Hamiltonian {
+    // Build the cycle: along the first column back to the top, then in rows
+    // back and forth over the remaining columns. Needs an even number of
+    // rows, or of columns for the transposed cycle.
+    fn build(field: &Field) -> Option<Vec<(u16, u16)>> {
+        let (w, h) = (field.width(), field.height());
+        if field.walls().next().is_some() || w < 2 || h < 2 {
+            return None;
+        }
+        let transpose = h % 2 != 0;
+        let (cols, rows) = if transpose { (h, w) } else { (w, h) };
+        if rows % 2 != 0 {
+            return None;
+        }
+        let mut cycle = Vec::with_capacity(w as usize * h as usize);
+        for y in 0..rows {
+            let xs: Box<dyn Iterator<Item = u16>> = if y % 2 == 0 {
+                Box::new(1..cols)
+            } else {
+                Box::new((1..cols).rev())
+            };
+            cycle.extend(xs.map(|x| (x, y)));
+        }
+        cycle.extend((0..rows).rev().map(|y| (0, y)));
+
+        let cell = |(x, y): (u16, u16)| {
+            let (x, y) = if transpose { (y, x) } else { (x, y) };
+            (field.x_min + x, field.y_min + y)
+        };
+        let mut next = vec![(0, 0); cycle.len()];
+        for (i, pos) in cycle.iter().enumerate() {
+            let (x, y) = cell(*pos);
+            let index = (y - field.y_min) as usize * w as usize
+                + (x - field.x_min) as usize;
+            next[index] = cell(cycle[(i + 1) % cycle.len()]);
+        }
+        Some(next)
+    }
+}
+
+impl Controller for Hamiltonian {
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent {
+        if self.field.as_ref() != Some(field) {
+            self.field = Some(field.clone());
+            self.next = Hamiltonian::build(field);
+        }
+        let player = &state.snakes[snake];
+        if let (Some(next), Some(head)) = (&self.next, player.head()) {
+            let board = Board::new(state, field);
+            let index = board.index(head);
+            let target = index.map(|i| next[i]);
+            let event = board
+                .moves(head, player.direction)
+                .into_iter()
+                .find(|(_, pos)| Some(*pos) == target);
+            if let Some((event, _)) = event {
+                return event;
+            }
+        }
+        Pathfinder.next_event(state, field, snake)
+    }
+}
+
+const MCTS_ITERATIONS: u32 = 500;
+// Ticks played after leaving the search tree.
+const ROLLOUT_TICKS: u32 = 30;
+// Weight of trying rarely played moves against replaying good ones.
+const EXPLORATION: f64 = 0.3;
+
+// How long `Mcts` searches for each move.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Budget {
+    Iterations(u32),
+    // Not available on the web, which has no clock.
+    Time(Duration),
+}
+
+// Monte Carlo tree search over the snake's own moves.
+//
+// Every iteration copies the state, follows the tree down to a move not
+// tried yet and plays on for a few ticks through `play`, with safe moves
+// that mostly head for food. All other snakes play such moves throughout.
+// Food in these games comes from generators forked from the bot's own, so
+// the real game's food is not affected. Games are rated by how long the
+// snake survives and how soon it eats, and the most visited move is played.
+#[derive(Debug, Clone)]
+pub struct Mcts {
+    budget: Budget,
+    rng: SeededRng,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts::new(Budget::Iterations(MCTS_ITERATIONS))
+    }
+}
+
+// Statistics of a sequence of moves in the search tree.
+#[derive(Default)]
+struct Node {
+    // Node reached by each of `MOVES`.
+    children: [Option<usize>; 4],
+    visits: u32,
+    value: f64,
+}
+
+impl Mcts {
+    pub fn new(budget: Budget) -> Self {
+        Mcts {
+            budget,
+            rng: SeededRng::new(0),
+        }
+    }
+
+    // Seed the bot's own generator, which decides its random moves and the
+    // food of its simulated games.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SeededRng::new(seed);
+        self
+    }
+
+    // Run one iteration from `root`, recording it in `nodes`.
+    fn iterate(
+        &mut self,
+        nodes: &mut Vec<Node>,
+        root: &GameState,
+        scratch: &mut GameState,
+        field: &Field,
+        snake: usize,
+    ) {
+        scratch.clone_from(root);
+        let mut rng = self.rng.fork();
+        let mut path = vec![0];
+        let mut result;
+        let mut events = vec![PlayerEvent::Idle; scratch.snakes.len()];
+        let score = root.snakes[snake].score;
+        // Ticks played, and the tick the snake first ate on
+        let mut ticks = 0;
+        let mut meal = None;
+
+        // Follow the tree down to a new node
+        loop {
+            let node = path[path.len() - 1];
+            let choices = safe_moves(scratch, snake, field);
+            let untried: Vec<usize> = choices
+                .iter()
+                .copied()
+                .filter(|&m| nodes[node].children[m].is_none())
+                .collect();
+            let expand = !untried.is_empty();
+            let chosen = if expand {
+                untried[rng.index(untried.len())]
+            } else {
+                let parent = nodes[node].visits as f64;
+                let ucb = |m: usize| {
+                    let child = &nodes[nodes[node].children[m].unwrap()];
+                    let visits = child.visits.max(1) as f64;
+                    child.value / visits
+                        + EXPLORATION * (parent.ln() / visits).sqrt()
+                };
+                *choices
+                    .iter()
+                    .max_by(|a, b| ucb(**a).total_cmp(&ucb(**b)))
+                    .expect("a snake has moves")
+            };
+            let child = match nodes[node].children[chosen] {
+                Some(child) => child,
+                None => {
+                    nodes.push(Node::default());
+                    nodes[node].children[chosen] = Some(nodes.len() - 1);
+                    nodes.len() - 1
+                }
+            };
+            path.push(child);
+
+            random_moves(scratch, field, &mut rng, &mut events);
+            events[snake] = MOVES[chosen].0;
+            result = play(scratch, field, &events, &mut rng);
+            ticks += 1;
+            if meal.is_none() && scratch.snakes[snake].score > score {
+                meal = Some(ticks);
+            }
+            if expand || result != GameResult::Continue {
+                break;
+            }
+        }
+
+        // Play on without the tree
+        let horizon = ticks + ROLLOUT_TICKS;
+        while ticks < horizon
+            && result == GameResult::Continue
+            && scratch.snakes[snake].alive
+        {
+            random_moves(scratch, field, &mut rng, &mut events);
+            result = play(scratch, field, &events, &mut rng);
+            ticks += 1;
+            if meal.is_none() && scratch.snakes[snake].score > score {
+                meal = Some(ticks);
+            }
+        }
+
+        let player = &scratch.snakes[snake];
+        let value = match result {
+            GameResult::GameOver(Outcome::Win) => 1.0,
+            GameResult::GameOver(Outcome::Winner(winner))
+                if winner == snake =>
+            {
+                1.0
+            }
+            // Dying later is less bad
+            _ if !player.alive => 0.25 * ticks as f64 / horizon as f64,
+            // Eating sooner is better, and ending closer to food otherwise
+            _ => match meal {
+                Some(tick) => 1.0 - 0.5 * tick as f64 / horizon as f64,
+                None => {
+                    let span = (field.width() + field.height()) as f64;
+                    let distance = player.head().map_or(span, |head| {
+                        food_distance(&scratch.food, head) as f64
+                    });
+                    0.5 - 0.25 * distance.min(span) / span
+                }
+            },
+        };
+        for node in path {
+            nodes[node].visits += 1;
+            nodes[node].value += value;
+        }
+    }
+}
+
+impl Controller for Mcts {
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent {
+        let player = &state.snakes[snake];
+        if player.head().is_none() || !player.alive {
+            return PlayerEvent::Idle;
+        }
+        let mut root = state.clone();
+        if root.grid.is_empty() {
+            root.rebuild_grid(field);
+        }
+        // Only read the clock for a time budget
+        let start = matches!(self.budget, Budget::Time(_)).then(Instant::now);
+        let mut nodes = vec![Node::default()];
+        let mut scratch = root.clone();
+        let mut iterations = 0;
+        while match (self.budget, start) {
+            (Budget::Iterations(limit), _) => iterations < limit,
+            (Budget::Time(limit), Some(start)) => start.elapsed() < limit,
+            (Budget::Time(_), None) => false,
+        } {
+            self.iterate(&mut nodes, &root, &mut scratch, field, snake);
+            iterations += 1;
+        }
+
+        let best = (0..MOVES.len())
+            .filter_map(|m| nodes[0].children[m].map(|child| (m, child)))
+            .max_by_key(|(_, child)| nodes[*child].visits);
+        best.map_or(PlayerEvent::Idle, |(m, _)| MOVES[m].0)
+    }
+}
+
+// Indices into `MOVES` of the moves of `snake`, leaving out reversing.
+fn moves(snake: &Snake, field: &Field) -> Vec<usize> {
+    let Some(head) = snake.head() else {
+        return Vec::new();
+    };
+    let direction = snake.direction;
+    let back = field.advance(head, -direction.0, -direction.1);
+    (0..MOVES.len())
+        .filter(|&m| {
+            let (dx, dy) = MOVES[m].1;
+            direction == (0, 0) || field.advance(head, dx, dy) != back
+        })
+        .collect()
+}
+
+// Indices into `MOVES` of the moves of snake `snake` that do not run into a
+// wall or body right away, or of all its moves if each of them does.
+fn safe_moves(state: &GameState, snake: usize, field: &Field) -> Vec<usize> {
+    let player = &state.snakes[snake];
+    let Some(head) = player.head() else {
+        return Vec::new();
+    };
+    let all = moves(player, field);
+    let safe: Vec<usize> = all
+        .iter()
+        .copied()
+        .filter(|&m| {
+            let (dx, dy) = MOVES[m].1;
+            state.grid.is_open(field.advance(head, dx, dy))
+        })
+        .collect();
+    if safe.is_empty() { all } else { safe }
+}
+
+// Pick a safe move for every living snake, mostly the one towards the
+// nearest food.
+fn random_moves(
+    state: &GameState,
+    field: &Field,
+    rng: &mut SeededRng,
+    events: &mut [PlayerEvent],
+) {
+    for (i, snake) in state.snakes.iter().enumerate() {
+        events[i] = PlayerEvent::Idle;
+        let Some(head) = snake.head().filter(|_| snake.alive) else {
+            continue;
+        };
+        let safe = safe_moves(state, i, field);
+        if safe.is_empty() {
+            continue;
+        }
+        let distance = |m: usize| {
+            let (dx, dy) = MOVES[m].1;
+            food_distance(&state.food, field.advance(head, dx, dy))
+        };
+        let chosen = if rng.index(4) == 0 {
+            safe[rng.index(safe.len())]
+        } else {
+            *safe
+                .iter()
+                .min_by_key(|m| distance(**m))
+                .expect("not empty")
+        };
+        events[i] = MOVES[chosen].0;
+    }
+}
+
+// Manhattan distance from `pos` to the nearest food, or `u32::MAX` if there
+// is none.
+fn food_distance(food: &[Food], pos: (u16, u16)) -> u32 {
+    food.iter()
+        .map(|f| {
+            pos.0.abs_diff(f.pos.0) as u32 + pos.1.abs_diff(f.pos.1) as u32
+        })
+        .min()
+        .unwrap_or(u32::MAX)
+}
+
+// Occupancy of the field as seen by a bot.
+struct Board<'a> {
+    field: &'a Field,
+    blocked: Vec<bool>,
+    // Food that no snake has eaten yet.
+    targets: Vec<(u16, u16)>,
+}
+
+impl<'a> Board<'a> {
+    fn new(state: &GameState, field: &'a Field) -> Self {
+        let cells = field.width() as usize * field.height() as usize;
+        let mut board = Board {
+            field,
+            blocked: Vec::new(),
+            targets: Vec::new(),
+        };
+        let mut blocked = vec![false; cells];
+        for pos in field.walls() {
+            board.set(&mut blocked, *pos, true);
+        }
+        for pos in state.alive().flat_map(|snake| &snake.body) {
+            board.set(&mut blocked, *pos, true);
+        }
+        board.targets = state
+            .food
+            .iter()
+            .map(|food| food.pos)
+            .filter(|pos| !board.is_blocked(&blocked, *pos))
+            .collect();
+        // Tails move on, unless the snake grows onto them
+        for snake in state.alive().filter(|snake| snake.body.len() > 1) {
+            let tail = snake.body[snake.body.len() - 1];
+            if !state.food.iter().any(|food| food.pos == tail) {
+                board.set(&mut blocked, tail, false);
+            }
+        }
+        board.blocked = blocked;
+        board
+    }
+
+    fn index(&self, pos: (u16, u16)) -> Option<usize> {
+        if !self.field.contains(pos) {
+            return None;
+        }
+        let x = (pos.0 - self.field.x_min) as usize;
+        let y = (pos.1 - self.field.y_min) as usize;
+        Some(y * self.field.width() as usize + x)
+    }
+
+    fn set(&self, blocked: &mut [bool], pos: (u16, u16), value: bool) {
+        if let Some(i) = self.index(pos) {
+            blocked[i] = value;
+        }
+    }
+
+    // Whether `pos` is outside the field or blocked in `blocked`.
+    fn is_blocked(&self, blocked: &[bool], pos: (u16, u16)) -> bool {
+        self.index(pos).is_none_or(|i| blocked[i])
+    }
+
+    // Neighbours of `pos` within the field.
+    fn neighbours(
+        &self,
+        pos: (u16, u16),
+    ) -> impl Iterator<Item = (PlayerEvent, (u16, u16))> + '_ {
+        MOVES.iter().filter_map(move |(event, (dx, dy))| {
+            let next = self.field.advance(pos, *dx, *dy);
+            self.field.contains(next).then_some((*event, next))
+        })
+    }
+
+    // Neighbours of a head heading in `direction`; a snake cannot reverse.
+    fn ahead(
+        &self,
+        head: (u16, u16),
+        direction: (i16, i16),
+    ) -> impl Iterator<Item = (PlayerEvent, (u16, u16))> + '_ {
+        let back = self.field.advance(head, -direction.0, -direction.1);
+        self.neighbours(head)
+            .filter(move |(_, pos)| direction == (0, 0) || *pos != back)
+    }
+
+    // Free cells a snake at `head` heading in `direction` can move to.
+    fn moves(
+        &self,
+        head: (u16, u16),
+        direction: (i16, i16),
+    ) -> Vec<(PlayerEvent, (u16, u16))> {
+        self.ahead(head, direction)
+            .filter(|(_, pos)| !self.is_blocked(&self.blocked, *pos))
+            .collect()
+    }
+
+    fn food_distance(&self, pos: (u16, u16)) -> u32 {
+        self.targets
+            .iter()
+            .map(|food| {
+                pos.0.abs_diff(food.0) as u32 + pos.1.abs_diff(food.1) as u32
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    // Shortest path from `from` to the nearest cell accepted by `goal`,
+    // without `from` itself. Goal cells may be blocked, other cells not.
+    fn path(
+        &self,
+        blocked: &[bool],
+        from: (u16, u16),
+        direction: (i16, i16),
+        goal: impl Fn((u16, u16)) -> bool,
+    ) -> Option<Vec<(u16, u16)>> {
+        let mut parents: Vec<Option<(u16, u16)>> = vec![None; blocked.len()];
+        let mut queue = VecDeque::new();
+        for (_, pos) in self.ahead(from, direction) {
+            if goal(pos) {
+                return Some(vec![pos]);
+            }
+            let i = self.index(pos)?;
+            if !blocked[i] {
+                parents[i] = Some(from);
+                queue.push_back(pos);
+            }
+        }
+        while let Some(pos) = queue.pop_front() {
+            if goal(pos) {
+                let mut path = vec![pos];
+                let mut cell = pos;
+                while let Some(parent) = parents[self.index(cell)?]
+                    && parent != from
+                {
+                    path.push(parent);
+                    cell = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for (_, next) in self.neighbours(pos) {
+                let i = self.index(next)?;
+                if parents[i].is_some() || next == from {
+                    continue;
+                }
+                if goal(next) || !blocked[i] {
+                    parents[i] = Some(pos);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    // Number of free cells reachable from `from`, including itself.
+    fn reachable(&self, blocked: &[bool], from: (u16, u16)) -> usize {
+        let mut seen = blocked.to_vec();
+        let mut queue = VecDeque::from([from]);
+        let mut count = 0;
+        if let Some(i) = self.index(from) {
+            seen[i] = true;
+        }
+        while let Some(pos) = queue.pop_front() {
+            count += 1;
+            for (_, next) in self.neighbours(pos) {
+                if let Some(i) = self.index(next)
+                    && !seen[i]
+                {
+                    seen[i] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        count
+    }
+
+    // Whether `snake` can still reach its own tail after following `path`,
+    // growing by one segment at its end if `grows`.
+    fn can_reach_tail(
+        &self,
+        snake: &Snake,
+        path: &[(u16, u16)],
+        grows: bool,
+    ) -> bool {
+        let mut blocked = self.blocked.clone();
+        let mut body = snake.body.clone();
+        for pos in &body {
+            self.set(&mut blocked, *pos, true);
+        }
+        for (i, pos) in path.iter().enumerate() {
+            body.push_front(*pos);
+            self.set(&mut blocked, *pos, true);
+            if !(grows && i + 1 == path.len())
+                && let Some(tail) = body.pop_back()
+            {
+                self.set(&mut blocked, tail, false);
+            }
+        }
+        let (head, tail) = (body[0], body[body.len() - 1]);
+        if head == tail {
+            return true;
+        }
+        self.path(&blocked, head, (0, 0), |pos| pos == tail)
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hamiltonian_cycle_follows_the_walls_of_each_field() {
+        let open = Field::new(4, 4);
+        let walled = Field::from_level("....\n.#..\n....\n....").unwrap();
+        let mut state = GameState::new();
+        state.snakes[0].body.push_back((3, 3));
+        let mut bot = Hamiltonian::default();
+        bot.next_event(&state, &open, 0);
+        assert!(bot.next.is_some());
+        bot.next_event(&state, &walled, 0);
+        assert!(bot.next.is_none());
+        bot.next_event(&state, &open, 0);
+        assert!(bot.next.is_some());
+    }
+}
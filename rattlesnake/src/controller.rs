@@ -0,0 +1,49 @@
+//! Players that choose their moves from the game state.
+use crate::bots::{Budget, Greedy, Hamiltonian, Mcts, Pathfinder};
+use crate::{Field, GameState, PlayerEvent};
+use std::time::Duration;
+
+// Source of the events for one snake.
+pub trait Controller {
+    // Choose the event of snake `snake` for the next tick.
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent;
+
+    // Describe what went wrong since the last call, e.g. a bot process that
+    // did not answer in time. Controllers keep playing after errors.
+    fn take_errors(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// Names of the built-in bots, as accepted by `bot`.
+pub const BOTS: [&str; 4] = ["greedy", "pathfinder", "hamiltonian", "mcts"];
+
+// Create the built-in bot called `name`. The search budget of `mcts` can
+// follow its name, in iterations as in `mcts:2000` or in milliseconds as in
+// `mcts:50ms`.
+pub fn bot(name: &str) -> Option<Box<dyn Controller>> {
+    match name.split_once(':') {
+        None => match name {
+            "greedy" => Some(Box::new(Greedy)),
+            "pathfinder" => Some(Box::new(Pathfinder)),
+            "hamiltonian" => Some(Box::new(Hamiltonian::default())),
+            "mcts" => Some(Box::new(Mcts::default())),
+            _ => None,
+        },
+        Some(("mcts", budget)) => {
+            let budget = match budget.strip_suffix("ms") {
+                Some(millis) => {
+                    Budget::Time(Duration::from_millis(millis.parse().ok()?))
+                }
+                None => Budget::Iterations(budget.parse().ok()?),
+            };
+            Some(Box::new(Mcts::new(budget)))
+        }
+        Some(_) => None,
+    }
+}
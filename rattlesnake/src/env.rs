@@ -0,0 +1,323 @@
+//! Reinforcement-learning environment.
+//!
+//! `Env` wraps a single-player game behind the `reset`/`step` interface of
+//! Gym-style environments. Observations are flat grids with one channel per
+//! kind of cell, ready to be fed to a neural network.
+use crate::{
+    Field, GameEvent, GameResult, GameState, Outcome, PlayerEvent, SeededRng,
+    play,
+};
+
+// Number of observation channels, see `Channel`.
+pub const CHANNELS: usize = 4;
+
+// Channels of an observation, in the order they are laid out.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Channel {
+    Body = 0,
+    Head = 1,
+    Food = 2,
+    Walls = 3,
+}
+
+// The moves available to an agent, either absolute or relative to the
+// snake's direction.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Straight,
+    TurnLeft,
+    TurnRight,
+}
+
+impl Action {
+    // The action space of absolute moves.
+    pub const ABSOLUTE: [Action; 4] =
+        [Action::Up, Action::Down, Action::Left, Action::Right];
+    // The smaller action space of relative moves.
+    pub const RELATIVE: [Action; 3] =
+        [Action::Straight, Action::TurnLeft, Action::TurnRight];
+
+    // The action numbered `index` in `Action::ABSOLUTE`.
+    pub fn from_index(index: usize) -> Option<Action> {
+        Action::ABSOLUTE.get(index).copied()
+    }
+
+    // The action numbered `index` in `Action::RELATIVE`.
+    pub fn from_relative_index(index: usize) -> Option<Action> {
+        Action::RELATIVE.get(index).copied()
+    }
+
+    // The event taking the action for a snake heading in `direction`. A
+    // snake that has not moved yet goes straight up, the way turns take it
+    // to face, see `find_direction`.
+    pub fn event(self, direction: (i16, i16)) -> PlayerEvent {
+        match self {
+            Action::Up => PlayerEvent::Up,
+            Action::Down => PlayerEvent::Down,
+            Action::Left => PlayerEvent::Left,
+            Action::Right => PlayerEvent::Right,
+            Action::Straight if direction == (0, 0) => PlayerEvent::Up,
+            Action::Straight => PlayerEvent::Idle,
+            Action::TurnLeft => PlayerEvent::TurnLeft,
+            Action::TurnRight => PlayerEvent::TurnRight,
+        }
+    }
+}
+
+// Reward for each thing that can happen during a step. The reward of a step
+// is the sum of all that apply.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Rewards {
+    // Eating food.
+    pub food: f32,
+    // Dying.
+    pub death: f32,
+    // Filling the field.
+    pub win: f32,
+    // Every step survived.
+    pub step: f32,
+    // Moving one cell closer to the nearest food; moving away costs as much.
+    pub approach: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            food: 1.0,
+            death: -1.0,
+            win: 1.0,
+            step: 0.0,
+            approach: 0.0,
+        }
+    }
+}
+
+// Cells of the field by channel, row-major within each channel. A cell is
+// 1.0 where the channel applies and 0.0 elsewhere.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl Observation {
+    // Observe any game, e.g. one with several snakes.
+    pub fn new(field: &Field, state: &GameState) -> Self {
+        let (width, height) = (field.width() as usize, field.height() as usize);
+        let mut observation = Observation {
+            width,
+            height,
+            data: vec![0.0; CHANNELS * width * height],
+        };
+        Observation::write(field, state, &mut observation.data);
+        observation
+    }
+
+    // Write the observation into `data`, which must hold `CHANNELS` times
+    // the cells of the field, to avoid allocating on every step.
+    pub fn write(field: &Field, state: &GameState, data: &mut [f32]) {
+        let (width, height) = (field.width() as usize, field.height() as usize);
+        assert_eq!(data.len(), CHANNELS * width * height);
+        data.fill(0.0);
+        let mut set = |channel: Channel, pos: &(u16, u16)| {
+            let x = (pos.0 - field.x_min) as usize;
+            let y = (pos.1 - field.y_min) as usize;
+            data[(channel as usize * height + y) * width + x] = 1.0;
+        };
+        for pos in field.walls() {
+            set(Channel::Walls, pos);
+        }
+        for food in &state.food {
+            set(Channel::Food, &food.pos);
+        }
+        for snake in state.alive() {
+            for pos in &snake.body {
+                set(Channel::Body, pos);
+            }
+            if let Some(head) = snake.head() {
+                set(Channel::Head, &head);
+            }
+        }
+    }
+
+    // Value of the cell at column `x` and row `y`, counted from zero.
+    pub fn get(&self, channel: Channel, x: usize, y: usize) -> f32 {
+        self.data[(channel as usize * self.height + y) * self.width + x]
+    }
+}
+
+// Details about a step that are not part of the reward.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Info {
+    pub score: u8,
+    pub length: usize,
+    // Steps since the last reset.
+    pub steps: usize,
+    // How the game ended, if it did.
+    pub outcome: Option<Outcome>,
+    // Whether the episode was cut off by the step limit.
+    pub truncated: bool,
+}
+
+pub struct Env {
+    field: Field,
+    rewards: Rewards,
+    step_limit: Option<usize>,
+    state: GameState,
+    rng: SeededRng,
+    steps: usize,
+    outcome: Option<Outcome>,
+}
+
+impl Env {
+    pub fn new(field: Field) -> Self {
+        let mut env = Env {
+            field,
+            rewards: Rewards::default(),
+            step_limit: None,
+            state: GameState::new(),
+            rng: SeededRng::new(0),
+            steps: 0,
+            outcome: None,
+        };
+        env.reset(0);
+        env
+    }
+
+    pub fn with_rewards(mut self, rewards: Rewards) -> Self {
+        self.rewards = rewards;
+        self
+    }
+
+    // End episodes after `steps` steps, so that agents that learned to loop
+    // forever still get reset.
+    pub fn with_step_limit(mut self, steps: usize) -> Self {
+        self.step_limit = Some(steps);
+        self
+    }
+
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    // Start a new episode and return its first observation.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.state = GameState::new();
+        self.rng = SeededRng::new(seed);
+        self.steps = 0;
+        self.outcome = None;
+        // Place the snake and the food; a new snake does not move yet
+        play(
+            &mut self.state,
+            &self.field,
+            &[PlayerEvent::Idle],
+            &mut self.rng,
+        );
+        self.observe()
+    }
+
+    // Advance the game by one tick. Once the episode is done, further steps
+    // leave the game as it is and give no reward.
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, Info) {
+        if self.outcome.is_some() || self.truncated() {
+            return (self.observe(), 0.0, true, self.info());
+        }
+
+        let distance = self.food_distance();
+        let event = action.event(self.state.snakes[0].direction);
+        let result =
+            play(&mut self.state, &self.field, &[event], &mut self.rng);
+        self.steps += 1;
+
+        let mut reward = self.rewards.step;
+        let ate = self
+            .state
+            .events()
+            .iter()
+            .any(|event| matches!(event, GameEvent::Ate { .. }));
+        if ate {
+            reward += self.rewards.food;
+        }
+        match result {
+            GameResult::Continue if !ate => {
+                // The nearest food changes when food is eaten
+                if let (Some(before), Some(after)) =
+                    (distance, self.food_distance())
+                {
+                    reward += self.rewards.approach
+                        * (before as f32 - after as f32).signum();
+                }
+            }
+            GameResult::GameOver(outcome) => {
+                reward += match outcome {
+                    Outcome::Win => self.rewards.win,
+                    _ => self.rewards.death,
+                };
+                self.outcome = Some(outcome);
+            }
+            GameResult::Continue => {}
+        }
+
+        let done = self.outcome.is_some() || self.truncated();
+        (self.observe(), reward, done, self.info())
+    }
+
+    pub fn observe(&self) -> Observation {
+        Observation::new(&self.field, &self.state)
+    }
+
+    fn info(&self) -> Info {
+        let snake = &self.state.snakes[0];
+        Info {
+            score: snake.score,
+            length: snake.body.len(),
+            steps: self.steps,
+            outcome: self.outcome,
+            truncated: self.outcome.is_none() && self.truncated(),
+        }
+    }
+
+    fn truncated(&self) -> bool {
+        self.step_limit.is_some_and(|limit| self.steps >= limit)
+    }
+
+    // Manhattan distance from the head to the nearest food that is not
+    // already eaten and waiting to be digested.
+    fn food_distance(&self) -> Option<u32> {
+        let head = self.state.snakes[0].head()?;
+        self.state
+            .food
+            .iter()
+            .map(|food| food.pos)
+            .filter(|pos| self.state.grid.segments(*pos) == 0)
+            .map(|pos| {
+                head.0.abs_diff(pos.0) as u32 + head.1.abs_diff(pos.1) as u32
+            })
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn straight_moves_a_snake_that_has_not_moved_yet() {
+        let mut env = Env::new(Field::new(9, 9));
+        env.reset(0);
+        let start = env.state().snakes[0].head();
+        env.step(Action::Straight);
+        let snake = &env.state().snakes[0];
+        assert_eq!(snake.direction, (0, -1));
+        assert_ne!(snake.head(), start);
+    }
+}
@@ -0,0 +1,71 @@
+//! What happened during a tick.
+use crate::{Death, FoodKind, PowerUp};
+use serde::{Deserialize, Serialize};
+
+// A change made by `play`, in the order it happened. Snakes are referred to
+// by their index in `GameState::snakes`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum GameEvent {
+    // The snake's head moved to `head` and its tail left `vacated`.
+    Moved {
+        snake: usize,
+        head: (u16, u16),
+        vacated: (u16, u16),
+    },
+    // The snake died; its body stays in the state but is off the board.
+    Died {
+        snake: usize,
+        death: Death,
+    },
+    // The snake digested food and its tail grew back onto `at`.
+    Grew {
+        snake: usize,
+        at: (u16, u16),
+    },
+    // The snake's head reached the food at `at`.
+    Ate {
+        snake: usize,
+        at: (u16, u16),
+        kind: FoodKind,
+    },
+    // Shrinking food cut the snake's tail off `vacated`.
+    Shrank {
+        snake: usize,
+        vacated: (u16, u16),
+    },
+    // New food appeared at `at`.
+    FoodSpawned {
+        at: (u16, u16),
+        kind: FoodKind,
+    },
+    // A magnet pulled food from `from` to `to`.
+    FoodMoved {
+        from: (u16, u16),
+        to: (u16, u16),
+    },
+    // The snake picked up `power`, which lasts for `ticks` ticks.
+    PoweredUp {
+        snake: usize,
+        power: PowerUp,
+        ticks: u32,
+    },
+    // The snake's `power` wore off.
+    PowerEnded {
+        snake: usize,
+        power: PowerUp,
+    },
+    // New food was due but no cell was free for it.
+    NoSpace,
+    // The food at `at` was left uneaten for too long and disappeared.
+    FoodExpired {
+        at: (u16, u16),
+    },
+    // The game was paused.
+    Paused,
+    // The paused game resumes after `remaining` more ticks.
+    Countdown {
+        remaining: u8,
+    },
+    // The game runs again; snakes move from the next tick on.
+    Resumed,
+}
@@ -0,0 +1,299 @@
+//! Bots running as separate processes.
+//!
+//! The protocol is line-delimited JSON over the bot's stdin and stdout. Each
+//! tick the engine writes one request line:
+//!
+//! ```text
+//! {"tick":1,"you":0,"field":{"x_min":1,"y_min":1,"x_max":40,"y_max":20,
+//!  "wrap":false,"walls":[[5,5]]},"snakes":[{"body":[[20,10]],
+//!  "direction":[0,0],"score":0,"alive":true}],"food":[[3,4]]}
+//! ```
+//!
+//! (on a single line), and the bot answers with one line naming its move for
+//! that tick, `up`, `down`, `left`, `right` or `idle`, or `turn_left` and
+//! `turn_right` to turn relative to the snake's direction:
+//!
+//! ```text
+//! {"tick":1,"move":"left"}
+//! ```
+//!
+//! Positions are `[x, y]` with `y` growing downwards, and `snakes[you]` is
+//! the bot's own snake. A bot that answers late, not at all or with a line
+//! that is not a valid reply idles for that tick. Answers to earlier ticks
+//! are skipped.
+use crate::{Boundary, Controller, Field, GameState, PlayerEvent};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+use std::{fmt, mem, thread};
+
+// Time the bot gets to answer at least for the first tick, to start up.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct Request<'a> {
+    tick: u64,
+    you: usize,
+    field: FieldInfo,
+    snakes: Vec<SnakeInfo<'a>>,
+    food: Vec<(u16, u16)>,
+}
+
+#[derive(Serialize)]
+struct FieldInfo {
+    x_min: u16,
+    y_min: u16,
+    x_max: u16,
+    y_max: u16,
+    wrap: bool,
+    walls: Vec<(u16, u16)>,
+}
+
+#[derive(Serialize)]
+struct SnakeInfo<'a> {
+    body: &'a VecDeque<(u16, u16)>,
+    direction: (i16, i16),
+    score: u8,
+    alive: bool,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    tick: u64,
+    #[serde(rename = "move")]
+    direction: Move,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+    TurnLeft,
+    TurnRight,
+    Idle,
+}
+
+impl From<Move> for PlayerEvent {
+    fn from(direction: Move) -> Self {
+        match direction {
+            Move::Up => PlayerEvent::Up,
+            Move::Down => PlayerEvent::Down,
+            Move::Left => PlayerEvent::Left,
+            Move::Right => PlayerEvent::Right,
+            Move::TurnLeft => PlayerEvent::TurnLeft,
+            Move::TurnRight => PlayerEvent::TurnRight,
+            Move::Idle => PlayerEvent::Idle,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExternalError {
+    // The request could not be sent, e.g. because the bot closed stdin.
+    Write(io::Error),
+    // No answer within the time limit.
+    Timeout { tick: u64 },
+    // The bot closed its stdout, usually by exiting.
+    Exited,
+    // The answer is not a valid reply.
+    Invalid { line: String, reason: String },
+}
+
+impl fmt::Display for ExternalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExternalError::Write(err) => {
+                write!(f, "cannot write to bot: {err}")
+            }
+            ExternalError::Timeout { tick } => {
+                write!(f, "bot did not answer in time on tick {tick}")
+            }
+            ExternalError::Exited => write!(f, "bot exited"),
+            ExternalError::Invalid { line, reason } => {
+                write!(f, "invalid reply {line:?}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExternalError {}
+
+// A bot process speaking the protocol above.
+pub struct ExternalBot {
+    child: Child,
+    stdin: ChildStdin,
+    // Lines the bot wrote, read on a separate thread so that waiting for an
+    // answer can time out.
+    replies: Receiver<String>,
+    timeout: Duration,
+    tick: u64,
+    // Set once the bot can no longer answer.
+    finished: bool,
+    errors: Vec<ExternalError>,
+}
+
+impl ExternalBot {
+    // Start `command`, a program followed by its arguments separated by
+    // whitespace. The bot gets `timeout` to answer each request; its stderr
+    // is discarded.
+    pub fn spawn(command: &str, timeout: Duration) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "empty bot command")
+        })?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(ExternalBot {
+            child,
+            stdin,
+            replies,
+            timeout,
+            tick: 0,
+            finished: false,
+            errors: Vec::new(),
+        })
+    }
+
+    // Send the request for the next tick.
+    fn send(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> io::Result<()> {
+        let request = Request {
+            tick: self.tick,
+            you: snake,
+            field: FieldInfo {
+                x_min: field.x_min,
+                y_min: field.y_min,
+                x_max: field.x_max,
+                y_max: field.y_max,
+                wrap: field.boundary() == Boundary::Wrap,
+                walls: field.walls().copied().collect(),
+            },
+            snakes: state
+                .snakes
+                .iter()
+                .map(|snake| SnakeInfo {
+                    body: &snake.body,
+                    direction: snake.direction,
+                    score: snake.score,
+                    alive: snake.alive,
+                })
+                .collect(),
+            food: state.food.iter().map(|food| food.pos).collect(),
+        };
+        let line = serde_json::to_string(&request).expect("request is JSON");
+        writeln!(self.stdin, "{line}")?;
+        self.stdin.flush()
+    }
+
+    // Wait for the answer to the current tick.
+    fn receive(&mut self) -> Result<PlayerEvent, ExternalError> {
+        let timeout = match self.tick {
+            1 => self.timeout.max(STARTUP_TIMEOUT),
+            _ => self.timeout,
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = match self.replies.recv_timeout(remaining) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(ExternalError::Timeout { tick: self.tick });
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(ExternalError::Exited);
+                }
+            };
+            match serde_json::from_str::<Reply>(&line) {
+                Ok(reply) if reply.tick == self.tick => {
+                    return Ok(reply.direction.into());
+                }
+                // A late answer to an earlier tick
+                Ok(reply) if reply.tick < self.tick => continue,
+                Ok(reply) => {
+                    return Err(ExternalError::Invalid {
+                        line,
+                        reason: format!("answers future tick {}", reply.tick),
+                    });
+                }
+                Err(err) => {
+                    return Err(ExternalError::Invalid {
+                        line,
+                        reason: err.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl Controller for ExternalBot {
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent {
+        if self.finished {
+            return PlayerEvent::Idle;
+        }
+        self.tick += 1;
+        let result = self
+            .send(state, field, snake)
+            .map_err(ExternalError::Write)
+            .and_then(|_| self.receive());
+        match result {
+            Ok(event) => event,
+            Err(err) => {
+                if matches!(
+                    err,
+                    ExternalError::Write(_) | ExternalError::Exited
+                ) {
+                    self.finished = true;
+                }
+                self.errors.push(err);
+                PlayerEvent::Idle
+            }
+        }
+    }
+
+    fn take_errors(&mut self) -> Vec<String> {
+        mem::take(&mut self.errors)
+            .iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,308 @@
+//! Kinds of food and how often each appears.
+//!
+//! Food rules are written as weights and settings, e.g.
+//! `normal=10,bonus=2,shrink=1,speed-up=1,slow-down=1,bonus-ticks=40` or
+//! `normal=10,ghost=1,magnet=1,slow-motion=1,power-ticks=20`; weights left
+//! out keep their defaults, which spawn normal food only.
+use crate::{POWER_TICKS, PowerUp, Random};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Score of bonus food; every other kind scores 1.
+pub const BONUS_SCORE: u8 = 3;
+// Tail segments shrinking food cuts, leaving at least the head.
+pub const SHRINK_SEGMENTS: usize = 3;
+// Milliseconds each speed-up food takes off the time between ticks, and
+// each slow-down food adds.
+pub const PACE_MILLIS: u64 = 20;
+// Speed-up food never makes ticks shorter than this.
+pub const FASTEST_MILLIS: u64 = 40;
+
+#[derive(
+    Debug, Default, PartialEq, Eq, Copy, Clone, Serialize, Deserialize,
+)]
+pub enum FoodKind {
+    #[default]
+    Normal,
+    // Worth `BONUS_SCORE`, but disappears when left uneaten for a while.
+    Bonus,
+    // Cuts `SHRINK_SEGMENTS` off the tail instead of growing the snake.
+    Shrink,
+    // Makes the game faster or slower by `PACE_MILLIS`.
+    SpeedUp,
+    SlowDown,
+    // Power-ups, which score nothing and do not grow the snake.
+    Ghost,
+    Magnet,
+    SlowMotion,
+}
+
+impl FoodKind {
+    pub const ALL: [FoodKind; KINDS] = [
+        FoodKind::Normal,
+        FoodKind::Bonus,
+        FoodKind::Shrink,
+        FoodKind::SpeedUp,
+        FoodKind::SlowDown,
+        FoodKind::Ghost,
+        FoodKind::Magnet,
+        FoodKind::SlowMotion,
+    ];
+
+    pub fn score(self) -> u8 {
+        match self {
+            FoodKind::Bonus => BONUS_SCORE,
+            FoodKind::Ghost | FoodKind::Magnet | FoodKind::SlowMotion => 0,
+            _ => 1,
+        }
+    }
+
+    // Whether the snake grows by a segment once the food is digested.
+    pub fn grows(self) -> bool {
+        self != FoodKind::Shrink && self.power().is_none()
+    }
+
+    // The power-up eating the food gives, if any.
+    pub fn power(self) -> Option<PowerUp> {
+        match self {
+            FoodKind::Ghost => Some(PowerUp::Ghost),
+            FoodKind::Magnet => Some(PowerUp::Magnet),
+            FoodKind::SlowMotion => Some(PowerUp::SlowMotion),
+            _ => None,
+        }
+    }
+
+    // Name of the kind in food rules.
+    pub fn name(self) -> &'static str {
+        match self {
+            FoodKind::Normal => "normal",
+            FoodKind::Bonus => "bonus",
+            FoodKind::Shrink => "shrink",
+            FoodKind::SpeedUp => "speed-up",
+            FoodKind::SlowDown => "slow-down",
+            FoodKind::Ghost => "ghost",
+            FoodKind::Magnet => "magnet",
+            FoodKind::SlowMotion => "slow-motion",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct Food {
+    pub pos: (u16, u16),
+    pub kind: FoodKind,
+    // Ticks left before the food disappears uneaten, if it ever does.
+    #[serde(default)]
+    pub ttl: Option<u32>,
+    // Eaten and lying under the body until the tail leaves it.
+    #[serde(default)]
+    pub digesting: bool,
+}
+
+impl Food {
+    // Normal food at `pos`.
+    pub fn new(pos: (u16, u16)) -> Self {
+        Food {
+            pos,
+            kind: FoodKind::Normal,
+            ttl: None,
+            digesting: false,
+        }
+    }
+}
+
+// Number of kinds of food.
+const KINDS: usize = 8;
+
+// Relative chances of each kind of food to spawn, how long bonus food lasts
+// and how long power-ups last.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FoodRules {
+    // Weights by kind, in the order of `FoodKind::ALL`.
+    #[serde(deserialize_with = "padded_weights")]
+    pub weights: [u32; KINDS],
+    pub bonus_ticks: u32,
+    pub power_ticks: u32,
+}
+
+impl Default for FoodRules {
+    fn default() -> Self {
+        let mut weights = [0; KINDS];
+        weights[FoodKind::Normal as usize] = 1;
+        FoodRules {
+            weights,
+            bonus_ticks: 50,
+            power_ticks: POWER_TICKS,
+        }
+    }
+}
+
+// Weights of rules written before every kind existed end early; the kinds
+// missing never spawn.
+fn padded_weights<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<[u32; KINDS], D::Error> {
+    let weights = Vec::<u32>::deserialize(deserializer)?;
+    if weights.len() > KINDS {
+        return Err(serde::de::Error::invalid_length(
+            weights.len(),
+            &"one weight per kind of food",
+        ));
+    }
+    let mut padded = [0; KINDS];
+    padded[..weights.len()].copy_from_slice(&weights);
+    Ok(padded)
+}
+
+impl FoodRules {
+    pub fn weight(&self, kind: FoodKind) -> u32 {
+        self.weights[kind as usize]
+    }
+
+    pub fn with_weight(mut self, kind: FoodKind, weight: u32) -> Self {
+        self.weights[kind as usize] = weight;
+        self
+    }
+
+    // Food of a random kind at `pos`. Rules that only ever spawn one kind
+    // draw nothing from `rng`.
+    pub(crate) fn spawn(&self, pos: (u16, u16), rng: &mut dyn Random) -> Food {
+        let kinds = FoodKind::ALL.iter().filter(|kind| self.weight(**kind) > 0);
+        let kind = if kinds.count() <= 1 {
+            FoodKind::ALL
+                .into_iter()
+                .find(|kind| self.weight(*kind) > 0)
+                .unwrap_or_default()
+        } else {
+            let total: u64 = self.weights.iter().map(|w| *w as u64).sum();
+            let total = usize::try_from(total).unwrap_or(usize::MAX);
+            let mut pick = rng.index(total) as u64;
+            FoodKind::ALL
+                .into_iter()
+                .find(|kind| {
+                    let weight = self.weight(*kind) as u64;
+                    let found = pick < weight;
+                    pick = pick.saturating_sub(weight);
+                    found
+                })
+                .unwrap_or_default()
+        };
+        Food {
+            pos,
+            kind,
+            ttl: (kind == FoodKind::Bonus).then_some(self.bonus_ticks),
+            digesting: false,
+        }
+    }
+}
+
+impl FromStr for FoodRules {
+    type Err = FoodError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut rules = FoodRules::default();
+        for setting in text.split(',').filter(|s| !s.is_empty()) {
+            let invalid = || FoodError::Invalid(setting.to_string());
+            let (key, value) = setting.split_once('=').ok_or_else(invalid)?;
+            let value: u32 = value.parse().map_err(|_| invalid())?;
+            match key {
+                "bonus-ticks" => rules.bonus_ticks = value,
+                "power-ticks" => rules.power_ticks = value,
+                _ => {
+                    let kind = FoodKind::ALL
+                        .into_iter()
+                        .find(|kind| kind.name() == key)
+                        .ok_or_else(|| {
+                            FoodError::UnknownSetting(key.to_string())
+                        })?;
+                    rules = rules.with_weight(kind, value);
+                }
+            }
+        }
+        if rules.weights.iter().all(|weight| *weight == 0) {
+            return Err(FoodError::NoFood);
+        }
+        let total: u64 = rules.weights.iter().map(|w| *w as u64).sum();
+        if total > u32::MAX as u64 {
+            return Err(FoodError::TooHeavy);
+        }
+        Ok(rules)
+    }
+}
+
+impl fmt::Display for FoodRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for kind in FoodKind::ALL {
+            write!(f, "{}={},", kind.name(), self.weight(kind))?;
+        }
+        write!(
+            f,
+            "bonus-ticks={},power-ticks={}",
+            self.bonus_ticks, self.power_ticks
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FoodError {
+    // The setting is neither a kind of food, `bonus-ticks` nor
+    // `power-ticks`.
+    UnknownSetting(String),
+    // A setting is not `name=number`.
+    Invalid(String),
+    // All weights are zero.
+    NoFood,
+    // The weights add up to more than `u32::MAX`.
+    TooHeavy,
+}
+
+impl fmt::Display for FoodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FoodError::UnknownSetting(key) => write!(
+                f,
+                "unknown food setting {key:?}, choose normal, bonus, shrink, \
+                 speed-up, slow-down, ghost, magnet, slow-motion, \
+                 bonus-ticks or power-ticks"
+            ),
+            FoodError::Invalid(setting) => {
+                write!(f, "invalid food setting {setting:?}")
+            }
+            FoodError::NoFood => write!(f, "no kind of food can spawn"),
+            FoodError::TooHeavy => {
+                write!(f, "food weights add up to more than {}", u32::MAX)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FoodError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRng;
+
+    #[test]
+    fn weights_adding_up_past_u32_are_rejected() {
+        let rules = "normal=4000000000,bonus=4000000000".parse::<FoodRules>();
+        assert_eq!(rules, Err(FoodError::TooHeavy));
+        let rules = "normal=4000000000,bonus=294967295".parse::<FoodRules>();
+        assert!(rules.is_ok());
+    }
+
+    #[test]
+    fn heavy_weights_still_spawn_every_kind() {
+        let rules = FoodRules::default()
+            .with_weight(FoodKind::Normal, u32::MAX)
+            .with_weight(FoodKind::Bonus, u32::MAX);
+        let mut rng = SeededRng::new(0);
+        let kinds: Vec<FoodKind> = (0..100)
+            .map(|_| rules.spawn((1, 1), &mut rng).kind)
+            .collect();
+        assert!(kinds.contains(&FoodKind::Normal));
+        assert!(kinds.contains(&FoodKind::Bonus));
+    }
+}
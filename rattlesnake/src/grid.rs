@@ -0,0 +1,273 @@
+//! Per-cell occupancy of the field.
+//!
+//! The grid mirrors walls, snake segments and food of a `GameState` so that
+//! looking up a cell takes constant time. It also keeps an index of the free
+//! cells, from which new food is drawn without scanning the field.
+use crate::random::Random;
+use std::fmt;
+
+const NOT_FREE: u32 = u32::MAX;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Cell {
+    wall: bool,
+    // Snake segments in the cell. Exceeds one only while a collision is
+    // resolved, or where a ghost crosses its own body.
+    segments: u16,
+    food: u16,
+}
+
+impl Cell {
+    fn is_free(&self) -> bool {
+        !self.wall && self.segments == 0 && self.food == 0
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Grid {
+    x_min: u16,
+    y_min: u16,
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    // Indices of the free cells in no particular order.
+    free: Vec<u32>,
+    // Position of each cell in `free`, or `NOT_FREE`.
+    slots: Vec<u32>,
+}
+
+impl Grid {
+    pub(crate) fn new(x_min: u16, y_min: u16, width: u16, height: u16) -> Self {
+        let len = width as usize * height as usize;
+        Grid {
+            x_min,
+            y_min,
+            width,
+            height,
+            cells: vec![Cell::default(); len],
+            free: (0..len as u32).collect(),
+            slots: (0..len as u32).collect(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub(crate) fn set_wall(&mut self, pos: (u16, u16)) {
+        self.update(pos, |cell| cell.wall = true);
+    }
+
+    pub(crate) fn add_segment(&mut self, pos: (u16, u16)) {
+        self.update(pos, |cell| cell.segments += 1);
+    }
+
+    pub(crate) fn remove_segment(&mut self, pos: (u16, u16)) {
+        self.update(pos, |cell| {
+            cell.segments = cell.segments.saturating_sub(1)
+        });
+    }
+
+    pub(crate) fn add_food(&mut self, pos: (u16, u16)) {
+        self.update(pos, |cell| cell.food += 1);
+    }
+
+    pub(crate) fn remove_food(&mut self, pos: (u16, u16)) {
+        self.update(pos, |cell| cell.food = cell.food.saturating_sub(1));
+    }
+
+    // Number of snake segments at `pos`.
+    pub(crate) fn segments(&self, pos: (u16, u16)) -> u16 {
+        self.index(pos).map_or(0, |i| self.cells[i].segments)
+    }
+
+    // Whether a head can move to `pos`: a cell of the grid without walls or
+    // segments.
+    pub(crate) fn is_open(&self, pos: (u16, u16)) -> bool {
+        self.index(pos).is_some_and(|i| {
+            let cell = &self.cells[i];
+            !cell.wall && cell.segments == 0
+        })
+    }
+
+    pub(crate) fn has_food(&self, pos: (u16, u16)) -> bool {
+        self.index(pos).is_some_and(|i| self.cells[i].food > 0)
+    }
+
+    // Pick a random free cell, or `None` if no cell is free.
+    pub(crate) fn random_free(
+        &self,
+        rng: &mut dyn Random,
+    ) -> Option<(u16, u16)> {
+        if self.free.is_empty() {
+            return None;
+        }
+        let i = self.free[rng.index(self.free.len())] as usize;
+        Some(self.position(i))
+    }
+
+    fn index(&self, pos: (u16, u16)) -> Option<usize> {
+        let x = pos.0.checked_sub(self.x_min)?;
+        let y = pos.1.checked_sub(self.y_min)?;
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+
+    fn position(&self, i: usize) -> (u16, u16) {
+        let w = self.width as usize;
+        (self.x_min + (i % w) as u16, self.y_min + (i / w) as u16)
+    }
+
+    // Apply `f` to the cell at `pos` and keep the free index in sync.
+    // Positions outside the grid are ignored.
+    fn update(&mut self, pos: (u16, u16), f: impl FnOnce(&mut Cell)) {
+        let Some(i) = self.index(pos) else {
+            return;
+        };
+        let was_free = self.cells[i].is_free();
+        f(&mut self.cells[i]);
+        match (was_free, self.cells[i].is_free()) {
+            (true, false) => {
+                let slot = self.slots[i] as usize;
+                let last = *self.free.last().expect("free cell is indexed");
+                self.free.swap_remove(slot);
+                if last as usize != i {
+                    self.slots[last as usize] = slot as u32;
+                }
+                self.slots[i] = NOT_FREE;
+            }
+            (false, true) => {
+                self.slots[i] = self.free.len() as u32;
+                self.free.push(i as u32);
+            }
+            _ => {}
+        }
+    }
+}
+
+// Cloning into an existing grid reuses its buffers, which keeps repeated
+// copies of a game state cheap.
+impl Clone for Grid {
+    fn clone(&self) -> Self {
+        Grid {
+            x_min: self.x_min,
+            y_min: self.y_min,
+            width: self.width,
+            height: self.height,
+            cells: self.cells.clone(),
+            free: self.free.clone(),
+            slots: self.slots.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.x_min = source.x_min;
+        self.y_min = source.y_min;
+        self.width = source.width;
+        self.height = source.height;
+        self.cells.clone_from(&source.cells);
+        self.free.clone_from(&source.free);
+        self.slots.clone_from(&source.slots);
+    }
+}
+
+impl fmt::Debug for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Grid")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("free", &self.free.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRng;
+
+    // Check that `free` lists exactly the free cells and that `slots`
+    // points into it.
+    fn assert_index(grid: &Grid) {
+        let mut free: Vec<u32> = (0..grid.cells.len() as u32)
+            .filter(|&i| grid.cells[i as usize].is_free())
+            .collect();
+        let mut listed = grid.free.clone();
+        listed.sort_unstable();
+        free.sort_unstable();
+        assert_eq!(listed, free);
+        for (i, slot) in grid.slots.iter().enumerate() {
+            if grid.cells[i].is_free() {
+                assert_eq!(grid.free[*slot as usize], i as u32);
+            } else {
+                assert_eq!(*slot, NOT_FREE);
+            }
+        }
+    }
+
+    #[test]
+    fn occupying_and_vacating_restores_free_cells() {
+        let mut grid = Grid::new(1, 1, 4, 3);
+        let cells = [(1, 1), (4, 3), (2, 2), (4, 1), (3, 2)];
+        for pos in cells {
+            grid.add_segment(pos);
+            assert_index(&grid);
+        }
+        // A second segment and food in a taken cell change nothing
+        grid.add_segment((2, 2));
+        grid.add_food((2, 2));
+        assert_eq!(grid.free.len(), 12 - cells.len());
+        grid.remove_segment((2, 2));
+        grid.remove_food((2, 2));
+        assert!(!grid.is_open((2, 2)));
+        assert_index(&grid);
+
+        for pos in cells.iter().rev() {
+            grid.remove_segment(*pos);
+            assert_index(&grid);
+        }
+        assert_eq!(grid.free.len(), 12);
+        for pos in cells {
+            grid.add_food(pos);
+            assert!(grid.has_food(pos));
+            grid.remove_food(pos);
+            assert!(!grid.has_food(pos));
+        }
+        assert_eq!(grid.free.len(), 12);
+        assert_index(&grid);
+    }
+
+    #[test]
+    fn walls_never_become_free() {
+        let mut grid = Grid::new(1, 1, 3, 3);
+        grid.set_wall((2, 2));
+        grid.set_wall((1, 3));
+        assert_index(&grid);
+        for pos in [(2, 2), (1, 3)] {
+            grid.add_segment(pos);
+            grid.add_food(pos);
+            grid.remove_segment(pos);
+            grid.remove_food(pos);
+            assert!(!grid.is_open(pos));
+        }
+        assert_index(&grid);
+        let mut rng = SeededRng::new(0);
+        for _ in 0..100 {
+            let pos = grid.random_free(&mut rng).expect("cells are free");
+            assert!(pos != (2, 2) && pos != (1, 3));
+        }
+    }
+
+    #[test]
+    fn full_grid_has_no_free_cell() {
+        let mut grid = Grid::new(1, 1, 3, 2);
+        for x in 1..=3 {
+            grid.add_segment((x, 1));
+            grid.add_food((x, 2));
+        }
+        let mut rng = SeededRng::new(0);
+        assert_eq!(grid.random_free(&mut rng), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Buffered player input.
+//!
+//! A tick moves a snake one cell, so it can take only one turn. Frontends
+//! read the keys pressed during a tick into an `InputQueue` and pass `play`
+//! one turn per tick, which keeps quick presses like up then left from
+//! being lost.
+use crate::{PlayerEvent, find_direction};
+use std::collections::VecDeque;
+
+// Number of inputs a queue holds; presses beyond it are dropped.
+pub const INPUT_QUEUE_LEN: usize = 3;
+
+#[derive(Debug, Default, Clone)]
+pub struct InputQueue {
+    events: VecDeque<PlayerEvent>,
+}
+
+impl InputQueue {
+    pub fn new() -> Self {
+        InputQueue::default()
+    }
+
+    // Queue a direction or turn. Other events are not queued; neither are
+    // inputs once the queue is full. Returns whether `event` was queued.
+    pub fn push(&mut self, event: PlayerEvent) -> bool {
+        let is_move = matches!(
+            event,
+            PlayerEvent::Up
+                | PlayerEvent::Down
+                | PlayerEvent::Left
+                | PlayerEvent::Right
+                | PlayerEvent::TurnLeft
+                | PlayerEvent::TurnRight
+        );
+        if !is_move || self.events.len() >= INPUT_QUEUE_LEN {
+            return false;
+        }
+        self.events.push_back(event);
+        true
+    }
+
+    // The event for the next tick of a snake heading in `direction`: the
+    // oldest input that changes it. Inputs reversing into the neck or
+    // keeping the direction are dropped on the way, and `Idle` is returned
+    // once the queue is empty.
+    pub fn next(&mut self, direction: (i16, i16)) -> PlayerEvent {
+        while let Some(event) = self.events.pop_front() {
+            if find_direction(&event, direction) != direction {
+                return event;
+            }
+        }
+        PlayerEvent::Idle
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RIGHT: (i16, i16) = (1, 0);
+    const UP: (i16, i16) = (0, -1);
+
+    #[test]
+    fn queue_holds_three_moves() {
+        let mut inputs = InputQueue::new();
+        for event in [PlayerEvent::Up, PlayerEvent::Left, PlayerEvent::Down] {
+            assert!(inputs.push(event));
+        }
+        assert!(!inputs.push(PlayerEvent::Right));
+        assert_eq!(inputs.len(), INPUT_QUEUE_LEN);
+    }
+
+    #[test]
+    fn only_moves_are_queued() {
+        let mut inputs = InputQueue::new();
+        assert!(!inputs.push(PlayerEvent::Pause));
+        assert!(!inputs.push(PlayerEvent::Idle));
+        assert!(inputs.push(PlayerEvent::TurnLeft));
+        assert_eq!(inputs.len(), 1);
+    }
+
+    #[test]
+    fn one_turn_is_taken_per_tick() {
+        let mut inputs = InputQueue::new();
+        inputs.push(PlayerEvent::Up);
+        inputs.push(PlayerEvent::Left);
+        assert_eq!(inputs.next(RIGHT), PlayerEvent::Up);
+        assert_eq!(inputs.next(UP), PlayerEvent::Left);
+        assert_eq!(inputs.next((-1, 0)), PlayerEvent::Idle);
+    }
+
+    #[test]
+    fn reversing_and_repeated_presses_are_dropped() {
+        let mut inputs = InputQueue::new();
+        inputs.push(PlayerEvent::Left);
+        inputs.push(PlayerEvent::Right);
+        inputs.push(PlayerEvent::Down);
+        assert_eq!(inputs.next(RIGHT), PlayerEvent::Down);
+        assert!(inputs.is_empty());
+
+        inputs.push(PlayerEvent::Left);
+        assert_eq!(inputs.next(RIGHT), PlayerEvent::Idle);
+    }
+
+    #[test]
+    fn clearing_drops_the_presses_made_while_paused() {
+        let mut inputs = InputQueue::new();
+        inputs.push(PlayerEvent::Up);
+        inputs.push(PlayerEvent::Left);
+        inputs.clear();
+        assert!(inputs.is_empty());
+        assert_eq!(inputs.next(RIGHT), PlayerEvent::Idle);
+        assert!(inputs.push(PlayerEvent::Down));
+    }
+}
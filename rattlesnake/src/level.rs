@@ -0,0 +1,165 @@
+//! Text-based level format.
+//!
+//! A level is a rectangular map of the playable area, one line per row:
+//!
+//! * `#` - wall
+//! * `.` - floor
+//! * `S` - floor where a snake starts, one per player in row-major order
+//! * `@` - floor with pre-placed food
+//!
+//! The field border is not part of the map; it is added around the map
+//! according to the field's `Boundary`. Without `S`, a single snake starts in
+//! the center of the field, or on the next floor tile if the center is a wall.
+use crate::Field;
+use std::fmt;
+
+const TILE_WALL: char = '#';
+const TILE_FLOOR: char = '.';
+const TILE_START: char = 'S';
+const TILE_FOOD: char = '@';
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LevelError {
+    // The level has no rows or no columns.
+    Empty,
+    // The level is too large to be addressed.
+    TooLarge,
+    // A row differs in width from the first row.
+    Ragged {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    // A character is not a known tile.
+    UnknownTile {
+        line: usize,
+        column: usize,
+        tile: char,
+    },
+    // No start is marked and the level has no floor to start on.
+    StartBlocked,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Empty => write!(f, "level is empty"),
+            LevelError::TooLarge => write!(f, "level is too large"),
+            LevelError::Ragged {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: expected {expected} tiles, found {found}"
+            ),
+            LevelError::UnknownTile { line, column, tile } => {
+                write!(f, "line {line}, column {column}: unknown tile {tile:?}")
+            }
+            LevelError::StartBlocked => {
+                write!(f, "no start position and no floor to start on")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+// Parse a level into a field with walls bounded by the level's size.
+pub(crate) fn parse(text: &str) -> Result<Field, LevelError> {
+    let mut rows: Vec<&str> =
+        text.lines().map(|line| line.trim_end()).collect();
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+
+    let width = match rows.first() {
+        Some(row) if !row.is_empty() => row.chars().count(),
+        _ => return Err(LevelError::Empty),
+    };
+    let height = rows.len();
+    // Keep room for the border around the map.
+    if width >= u16::MAX as usize - 1 || height >= u16::MAX as usize - 1 {
+        return Err(LevelError::TooLarge);
+    }
+
+    let mut field = Field::new(width as u16, height as u16);
+    for (r, row) in rows.iter().enumerate() {
+        let line = r + 1;
+        let found = row.chars().count();
+        if found != width {
+            return Err(LevelError::Ragged {
+                line,
+                expected: width,
+                found,
+            });
+        }
+        for (c, tile) in row.chars().enumerate() {
+            let column = c + 1;
+            let pos = (field.x_min + c as u16, field.y_min + r as u16);
+            match tile {
+                TILE_WALL => {
+                    field.walls.insert(pos);
+                }
+                TILE_FLOOR => {}
+                TILE_START => field.starts.push(pos),
+                TILE_FOOD => field.food.push(pos),
+                _ => {
+                    return Err(LevelError::UnknownTile { line, column, tile });
+                }
+            }
+        }
+    }
+
+    if field.starts(1).is_empty() {
+        return Err(LevelError::StartBlocked);
+    }
+
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_become_walls_starts_and_food() {
+        let field = parse("#.S\n.@.\nS.#\n").unwrap();
+        assert_eq!((field.width(), field.height()), (3, 3));
+        let mut walls: Vec<_> = field.walls().copied().collect();
+        walls.sort();
+        assert_eq!(walls, [(1, 1), (3, 3)]);
+        assert_eq!(field.starts, [(3, 1), (1, 3)]);
+        assert_eq!(field.food, [(2, 2)]);
+    }
+
+    #[test]
+    fn rows_must_be_as_wide_as_the_first() {
+        let error = LevelError::Ragged {
+            line: 2,
+            expected: 3,
+            found: 2,
+        };
+        assert_eq!(parse("...\n..\n...").unwrap_err(), error);
+    }
+
+    #[test]
+    fn unknown_tiles_are_rejected() {
+        let error = LevelError::UnknownTile {
+            line: 2,
+            column: 3,
+            tile: 'x',
+        };
+        assert_eq!(parse("...\n..x").unwrap_err(), error);
+    }
+
+    #[test]
+    fn levels_need_somewhere_to_start() {
+        assert_eq!(parse("###\n###").unwrap_err(), LevelError::StartBlocked);
+        assert_eq!(parse("\n\n").unwrap_err(), LevelError::Empty);
+        // Without a start, the snake starts on the floor nearest the center
+        let field = parse("###\n#.#\n###").unwrap();
+        assert!(field.starts.is_empty());
+        assert_eq!(field.starts(1), [(2, 2)]);
+    }
+}
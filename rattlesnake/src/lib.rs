@@ -1,12 +1,78 @@
 //! Snake game.
+use grid::Grid;
 use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
 
-#[derive(Debug)]
+// Types of the Battlesnake API, kept apart as they share names with the
+// engine's own.
+pub mod battlesnake;
+mod bots;
+mod controller;
+mod env;
+mod event;
+#[cfg(not(target_arch = "wasm32"))]
+mod external;
+mod food;
+mod grid;
+mod input;
+mod level;
+mod neural;
+mod outcome;
+mod power;
+mod random;
+mod replay;
+mod save;
+mod speed;
+
+pub use bots::{Budget, Greedy, Hamiltonian, Mcts, Pathfinder};
+pub use controller::{BOTS, Controller, bot};
+pub use env::{Action, CHANNELS, Channel, Env, Info, Observation, Rewards};
+pub use event::GameEvent;
+#[cfg(not(target_arch = "wasm32"))]
+pub use external::{ExternalBot, ExternalError};
+pub use food::{
+    BONUS_SCORE, FASTEST_MILLIS, Food, FoodError, FoodKind, FoodRules,
+    PACE_MILLIS, SHRINK_SEGMENTS,
+};
+pub use input::{INPUT_QUEUE_LEN, InputQueue};
+pub use level::LevelError;
+pub use neural::{
+    GENOME_VERSION, Genome, GenomeError, INPUTS, NeuralBot, OUTPUTS,
+};
+pub use outcome::{Death, Outcome};
+pub use power::{Effect, POWER_TICKS, PowerUp};
+pub use random::{Random, SeededRng};
+pub use replay::{REPLAY_VERSION, Replay, ReplayError, ReplayPlayer};
+pub use save::{SAVE_VERSION, SaveError, SavedGame};
+pub use speed::{Speed, SpeedError, TICK_MILLIS};
+
+// What happens when the snake leaves the field.
+#[derive(
+    Debug, Default, PartialEq, Eq, Copy, Clone, Serialize, Deserialize,
+)]
+pub enum Boundary {
+    // Leaving the field is a wall hit.
+    #[default]
+    Walls,
+    // Leaving the field re-enters on the opposite side.
+    Wrap,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Field {
     x_min: u16,
     x_max: u16,
     y_min: u16,
     y_max: u16,
+    boundary: Boundary,
+    walls: HashSet<(u16, u16)>,
+    starts: Vec<(u16, u16)>,
+    food: Vec<(u16, u16)>,
+    #[serde(default)]
+    speed: Speed,
+    #[serde(default)]
+    food_rules: FoodRules,
 }
 
 impl Field {
@@ -16,121 +82,649 @@ impl Field {
             x_max: width,
             y_min: 1,
             y_max: height,
+            boundary: Boundary::Walls,
+            walls: HashSet::new(),
+            starts: Vec::new(),
+            food: Vec::new(),
+            speed: Speed::default(),
+            food_rules: FoodRules::default(),
+        }
+    }
+
+    // Create a field from a text-based level, see `level` for the format.
+    pub fn from_level(text: &str) -> Result<Self, LevelError> {
+        level::parse(text)
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn with_speed(mut self, speed: Speed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    pub fn with_food_rules(mut self, rules: FoodRules) -> Self {
+        self.food_rules = rules;
+        self
+    }
+
+    pub fn food_rules(&self) -> FoodRules {
+        self.food_rules
+    }
+
+    pub fn width(&self) -> u16 {
+        self.x_max - self.x_min + 1
+    }
+
+    pub fn height(&self) -> u16 {
+        self.y_max - self.y_min + 1
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    pub fn walls(&self) -> impl Iterator<Item = &(u16, u16)> {
+        self.walls.iter()
+    }
+
+    pub fn is_wall(&self, pos: (u16, u16)) -> bool {
+        self.walls.contains(&pos)
+    }
+
+    // Number of cells a snake can move on.
+    pub fn floor(&self) -> usize {
+        self.width() as usize * self.height() as usize - self.walls.len()
+    }
+
+    // Positions the snakes of a `players` game start at.
+    //
+    // Snakes take the level's start positions in order. Any further snakes
+    // are spread evenly across the center row, moved forward to the next
+    // free cell where that spot is taken.
+    fn starts(&self, players: usize) -> Vec<(u16, u16)> {
+        let mut starts: Vec<(u16, u16)> =
+            self.starts.iter().take(players).cloned().collect();
+        let spread = players - starts.len();
+        let y = self.y_min + self.y_max / 2;
+        for i in 1..=spread {
+            let x = self.x_min
+                + (i as u32 * self.x_max as u32 / (spread as u32 + 1)) as u16;
+            let free = self
+                .cells_from((x, y))
+                .find(|pos| !self.is_wall(*pos) && !starts.contains(pos));
+            if let Some(pos) = free {
+                starts.push(pos);
+            }
+        }
+        starts
+    }
+
+    // Iterate over all cells in row-major order, starting at `pos` and
+    // wrapping around at the end of the field.
+    fn cells_from(
+        &self,
+        pos: (u16, u16),
+    ) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let (w, h) = (self.width() as usize, self.height() as usize);
+        let first =
+            (pos.1 - self.y_min) as usize * w + (pos.0 - self.x_min) as usize;
+        (0..w * h).map(move |i| {
+            let cell = (first + i) % (w * h);
+            (
+                self.x_min + (cell % w) as u16,
+                self.y_min + (cell / w) as u16,
+            )
+        })
+    }
+
+    // Check if `pos` lies within the field bounds.
+    fn contains(&self, pos: (u16, u16)) -> bool {
+        pos.0 >= self.x_min
+            && pos.0 <= self.x_max
+            && pos.1 >= self.y_min
+            && pos.1 <= self.y_max
+    }
+
+    // Position reached by moving `(dx, dy)` from `pos`.
+    //
+    // With walls, leaving the field yields a position outside of it, which
+    // `collided` reports as a wall hit. With wrap-around, the position
+    // re-enters on the opposite side.
+    fn advance(&self, pos: (u16, u16), dx: i16, dy: i16) -> (u16, u16) {
+        match self.boundary {
+            Boundary::Walls => (
+                ((pos.0 as i16) + dx).max(0) as u16,
+                ((pos.1 as i16) + dy).max(0) as u16,
+            ),
+            Boundary::Wrap => (
+                wrap(pos.0, dx, self.x_min, self.width()),
+                wrap(pos.1, dy, self.y_min, self.height()),
+            ),
         }
     }
 }
 
-#[derive(PartialEq, Copy, Clone)]
+// Move `v` by `delta` within `min..min + len`, wrapping at the ends.
+fn wrap(v: u16, delta: i16, min: u16, len: u16) -> u16 {
+    let offset = (v as i32 - min as i32 + delta as i32).rem_euclid(len as i32);
+    min + offset as u16
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum PlayerEvent {
     Up,
     Down,
     Left,
     Right,
+    // Turn a quarter to the left or right of the current direction.
+    TurnLeft,
+    TurnRight,
+    // Pause the game, or resume a paused game after a countdown.
+    Pause,
     Quit,
     Idle,
     ToggleArrowKeys,
+    SaveAndQuit,
+    // Hand the snake to the next built-in bot, or back to the player.
+    NextBot,
 }
 
+// Steps of the countdown before a paused game resumes.
+pub const COUNTDOWN: u8 = 3;
+// Time frontends wait between countdown steps.
+pub const COUNTDOWN_MILLIS: u64 = 1000;
+
+// Whether the game is running. While it is not, `play` only moves on to the
+// next phase and nothing else in the game changes.
+#[derive(
+    Debug, Default, PartialEq, Eq, Copy, Clone, Serialize, Deserialize,
+)]
+pub enum Phase {
+    #[default]
+    Running,
+    Paused,
+    // Steps left before the game runs again, counting down to 1.
+    Countdown(u8),
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum GameResult {
     Continue,
-    GameOver,
+    GameOver(Outcome),
 }
 
-#[derive(Debug, Default)]
-pub struct GameState {
-    pub snake: Vec<(u16, u16)>,
-    pub food: Vec<(u16, u16)>,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snake {
+    pub body: VecDeque<(u16, u16)>,
     pub direction: (i16, i16),
     pub score: u8,
+    pub alive: bool,
+    #[serde(default)]
+    pub death: Option<Death>,
 }
 
-impl GameState {
+impl Snake {
     pub fn new() -> Self {
-        GameState {
-            snake: Vec::new(),
-            food: Vec::new(),
+        Snake {
+            body: VecDeque::new(),
             direction: (0, 0),
             score: 0,
+            alive: true,
+            death: None,
+        }
+    }
+
+    pub fn head(&self) -> Option<(u16, u16)> {
+        self.body.front().copied()
+    }
+}
+
+// `clone_from` reuses the body's buffer, see `GameState`.
+impl Clone for Snake {
+    fn clone(&self) -> Self {
+        Snake {
+            body: self.body.clone(),
+            direction: self.direction,
+            score: self.score,
+            alive: self.alive,
+            death: self.death,
         }
     }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.body.clone_from(&source.body);
+        self.direction = source.direction;
+        self.score = source.score;
+        self.alive = source.alive;
+        self.death = source.death;
+    }
+}
+
+impl Default for Snake {
+    fn default() -> Self {
+        Snake::new()
+    }
 }
 
-pub type RandomRange = fn(u16, u16) -> u16;
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GameState {
+    pub snakes: Vec<Snake>,
+    // Food on the field, and food eaten but not digested yet under a body.
+    pub food: Vec<Food>,
+    #[serde(default)]
+    pub phase: Phase,
+    // Food of any kind eaten by all snakes, which speed curves go by.
+    #[serde(default)]
+    pub eaten: u32,
+    // Speed-up food eaten less slow-down food eaten.
+    #[serde(default)]
+    pub pace: i32,
+    // Power-ups in effect, in the order they were picked up.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    // Occupancy of the field, rebuilt from the rest of the state when empty.
+    #[serde(skip)]
+    grid: Grid,
+    #[serde(skip)]
+    events: Vec<GameEvent>,
+}
+
+// Bots that simulate many games copy states over and over, so `clone_from`
+// reuses every buffer of the target instead of allocating.
+impl Clone for GameState {
+    fn clone(&self) -> Self {
+        GameState {
+            snakes: self.snakes.clone(),
+            food: self.food.clone(),
+            phase: self.phase,
+            eaten: self.eaten,
+            pace: self.pace,
+            effects: self.effects.clone(),
+            grid: self.grid.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.snakes.clone_from(&source.snakes);
+        self.food.clone_from(&source.food);
+        self.phase = source.phase;
+        self.eaten = source.eaten;
+        self.pace = source.pace;
+        self.effects.clone_from(&source.effects);
+        self.grid.clone_from(&source.grid);
+        self.events.clone_from(&source.events);
+    }
+}
+
+impl GameState {
+    pub fn new() -> Self {
+        GameState::with_players(1)
+    }
+
+    pub fn with_players(players: usize) -> Self {
+        GameState {
+            snakes: (0..players).map(|_| Snake::new()).collect(),
+            food: Vec::new(),
+            phase: Phase::Running,
+            eaten: 0,
+            pace: 0,
+            effects: Vec::new(),
+            grid: Grid::default(),
+            events: Vec::new(),
+        }
+    }
+
+    // Milliseconds frontends wait before the next call to `play`: a
+    // countdown step, or the field's speed by the food eaten so far changed
+    // by the speed-up and slow-down food eaten, doubled in slow motion.
+    pub fn tick_millis(&self, field: &Field) -> u64 {
+        if let Phase::Countdown(_) = self.phase {
+            return COUNTDOWN_MILLIS;
+        }
+        let millis = field.speed.interval(self.eaten);
+        let paced = millis as i64 - self.pace as i64 * PACE_MILLIS as i64;
+        let paced = paced.max(FASTEST_MILLIS.min(millis) as i64) as u64;
+        let slow = self
+            .effects
+            .iter()
+            .any(|effect| effect.power == PowerUp::SlowMotion);
+        if slow { paced * 2 } else { paced }
+    }
+
+    // Whether snake `snake` has `power` in effect.
+    pub fn has_power(&self, snake: usize, power: PowerUp) -> bool {
+        self.effects
+            .iter()
+            .any(|effect| effect.snake == snake && effect.power == power)
+    }
+
+    // Iterate over the snakes that are still in the game.
+    pub fn alive(&self) -> impl Iterator<Item = &Snake> {
+        self.snakes.iter().filter(|snake| snake.alive)
+    }
+
+    // What happened during the last call to `play`.
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    // Build the grid from the field and the living snakes and food.
+    fn rebuild_grid(&mut self, field: &Field) {
+        let mut grid =
+            Grid::new(field.x_min, field.y_min, field.width(), field.height());
+        for pos in field.walls() {
+            grid.set_wall(*pos);
+        }
+        for pos in self.alive().flat_map(|snake| &snake.body) {
+            grid.add_segment(*pos);
+        }
+        for food in &self.food {
+            grid.add_food(food.pos);
+        }
+        self.grid = grid;
+    }
+
+    fn add_food(&mut self, food: Food) {
+        self.food.push(food);
+        self.grid.add_food(food.pos);
+        self.events.push(GameEvent::FoodSpawned {
+            at: food.pos,
+            kind: food.kind,
+        });
+    }
+
+    // Take the food at `i` off the field.
+    fn remove_food(&mut self, i: usize) -> Food {
+        let food = self.food.remove(i);
+        self.grid.remove_food(food.pos);
+        food
+    }
+}
 
 // Run the game with given settings.
+//
+// `events` holds one event per snake; snakes without an event stay idle.
+// All snakes move at once before collisions are resolved:
+//
+// * A snake dies when its head hits a wall, its own body or the body of
+//   another snake. Ghosts pass through their own body, see `PowerUp`.
+// * When two heads meet, the shorter snake dies; on equal length both do.
+//
+// Dead snakes are removed from the board. A single-player game ends when
+// its snake dies or fills the field, a multiplayer game when fewer than two
+// snakes are left. Any player quitting ends the game right away.
+//
+// Any player pausing freezes the game until a player pauses again, which
+// resumes it after `COUNTDOWN` more calls, see `Phase`.
+//
+// Everything that changed is listed in `GameState::events` afterwards.
 pub fn play(
     state: &mut GameState,
     field: &Field,
-    event: &PlayerEvent,
-    randomizer: &RandomRange,
+    events: &[PlayerEvent],
+    rng: &mut dyn Random,
 ) -> GameResult {
     debug!("Game state: {:?}", state);
     debug!("Field: {:?}", field);
+    state.events.clear();
 
-    let spawn = random_position(
-        randomizer,
-        field.x_min,
-        field.x_max + 1,
-        field.y_min,
-        field.y_max + 1,
-    );
-
-    // Initialize snake and food
-    if state.snake.is_empty() {
-        info!("Initializing snake...");
-        let initial_position =
-            (field.x_min + field.x_max / 2, field.y_min + field.y_max / 2);
-        state.snake.push(initial_position);
-        state.food.push(random_exclude(&spawn, &[initial_position]));
-    }
-
-    // Move snake
-    let past_tail = state.snake[state.snake.len() - 1];
-    state.direction = find_direction(event, state.direction);
-    locomote(&mut state.snake, state.direction.0, state.direction.1);
-    debug!("Moved snake {:?}", state.snake);
+    if events.iter().any(|event| {
+        matches!(event, PlayerEvent::Quit | PlayerEvent::SaveAndQuit)
+    }) {
+        info!("Quit game");
+        return GameResult::GameOver(Outcome::Quit);
+    }
 
-    // Detect collisions
-    if collided(&state.snake, field) {
-        return GameResult::GameOver;
+    // Initialize snakes and food
+    if state.snakes.iter().all(|snake| snake.body.is_empty()) {
+        info!("Initializing {} snake(s)...", state.snakes.len());
+        let starts = field.starts(state.snakes.len());
+        for (snake, start) in state.snakes.iter_mut().zip(&starts) {
+            snake.body.push_back(*start);
+        }
+        for snake in state.snakes.iter_mut().skip(starts.len()) {
+            // No room left on the field
+            snake.alive = false;
+        }
+        state.rebuild_grid(field);
+        for pos in &field.food {
+            state.add_food(Food::new(*pos));
+        }
+        if state.food.is_empty() {
+            spawn_food(state, field, rng);
+        }
+    } else if state.grid.is_empty() {
+        // Restore the grid of a deserialized state
+        state.rebuild_grid(field);
     }
 
-    // Eat food and spawn new
-    let head = state.snake[0];
-    for f_idx in 0..state.food.len() {
-        if head == state.food[f_idx] {
-            let exclude: Vec<(u16, u16)> = state
-                .snake
-                .iter()
-                .chain(&state.food)
-                .cloned()
-                .chain([past_tail])
-                .collect();
-            state.score = state.score.saturating_add(1);
-            let new_food = random_exclude(&spawn, &exclude);
-            state.food.push(new_food);
-            info!("Spawned food at {:?}", new_food);
+    // Pause, count down and resume
+    let pause = events.contains(&PlayerEvent::Pause);
+    let phase = match (state.phase, pause) {
+        (Phase::Running, false) => Phase::Running,
+        (Phase::Running | Phase::Countdown(_), true) => Phase::Paused,
+        (Phase::Paused, true) => Phase::Countdown(COUNTDOWN),
+        (Phase::Paused, false) => Phase::Paused,
+        (Phase::Countdown(remaining), false) if remaining > 1 => {
+            Phase::Countdown(remaining - 1)
         }
+        (Phase::Countdown(_), false) => Phase::Running,
+    };
+    if phase != state.phase {
+        info!("Game phase changed to {:?}", phase);
+        state.events.push(match phase {
+            Phase::Running => GameEvent::Resumed,
+            Phase::Paused => GameEvent::Paused,
+            Phase::Countdown(remaining) => GameEvent::Countdown { remaining },
+        });
     }
-    for f_idx in 0..state.food.len() {
-        if past_tail == state.food[f_idx] {
-            state.snake.push(past_tail);
-            state.food.remove(f_idx);
+    if state.phase != Phase::Running || phase != Phase::Running {
+        state.phase = phase;
+        return GameResult::Continue;
+    }
+
+    // Move snakes, vacating all tails before any head enters its new cell
+    let mut past_tails = Vec::with_capacity(state.snakes.len());
+    for (i, snake) in state.snakes.iter_mut().enumerate() {
+        let past_tail = snake.body.back().copied().unwrap_or_default();
+        past_tails.push(past_tail);
+        if !snake.alive {
+            continue;
+        }
+        let event = events.get(i).unwrap_or(&PlayerEvent::Idle);
+        snake.direction = find_direction(event, snake.direction);
+        locomote(&mut snake.body, field, snake.direction.0, snake.direction.1);
+        state.grid.remove_segment(past_tail);
+        debug!("Moved snake {} to {:?}", i, snake.head());
+        state.events.push(GameEvent::Moved {
+            snake: i,
+            head: snake.body[0],
+            vacated: past_tail,
+        });
+    }
+    for snake in state.snakes.iter().filter(|snake| snake.alive) {
+        state.grid.add_segment(snake.body[0]);
+    }
+
+    // Detect collisions
+    let deaths: Vec<(usize, Death)> = (0..state.snakes.len())
+        .filter(|&i| state.snakes[i].alive)
+        .filter_map(|i| collided(state, i, field, &past_tails).map(|d| (i, d)))
+        .collect();
+    for &(i, death) in &deaths {
+        let snake = &mut state.snakes[i];
+        snake.alive = false;
+        snake.death = Some(death);
+        for pos in &snake.body {
+            state.grid.remove_segment(*pos);
+        }
+        state.events.push(GameEvent::Died { snake: i, death });
+        // Power-ups end with the snake that picked them up
+        for effect in state.effects.iter().filter(|effect| effect.snake == i) {
+            state.events.push(GameEvent::PowerEnded {
+                snake: i,
+                power: effect.power,
+            });
+        }
+        state.effects.retain(|effect| effect.snake != i);
+    }
+    if let Some(outcome) = decide(state, &deaths) {
+        return GameResult::GameOver(outcome);
+    }
+
+    // Digest food: grow where the tail leaves food it ate. A snake chasing
+    // its tail grows into its own head; food under another snake's head
+    // waits for that snake's tail instead
+    for (i, past_tail) in past_tails.into_iter().enumerate() {
+        let snake = &state.snakes[i];
+        let taken = state.grid.segments(past_tail) > 0
+            && snake.head() != Some(past_tail);
+        if !snake.alive || taken || !state.grid.has_food(past_tail) {
+            continue;
+        }
+        if let Some(f_idx) = state
+            .food
+            .iter()
+            .position(|f| f.pos == past_tail && f.digesting)
+        {
+            state.snakes[i].body.push_back(past_tail);
+            state.grid.add_segment(past_tail);
+            state.remove_food(f_idx);
             info!("Removed food at {:?}", past_tail);
-            break;
+            state.events.push(GameEvent::Grew {
+                snake: i,
+                at: past_tail,
+            });
         }
     }
 
+    // Magnets pull food that is not being digested toward the head
+    for i in 0..state.snakes.len() {
+        if state.snakes[i].alive && state.has_power(i, PowerUp::Magnet) {
+            attract(state, i);
+        }
+    }
+
+    // Count down power-ups, ending those used up this tick
+    for effect in &mut state.effects {
+        effect.ticks = effect.ticks.saturating_sub(1);
+        if effect.ticks == 0 {
+            info!("{:?} of snake {} ended", effect.power, effect.snake);
+            state.events.push(GameEvent::PowerEnded {
+                snake: effect.snake,
+                power: effect.power,
+            });
+        }
+    }
+    state.effects.retain(|effect| effect.ticks > 0);
+
+    // Eat food and spawn new. Once the field has no room for food, the rest
+    // of the tick does not try again
+    let mut room = true;
+    for i in 0..state.snakes.len() {
+        let snake = &state.snakes[i];
+        let Some(head) = snake.head() else {
+            continue;
+        };
+        if !snake.alive || !state.grid.has_food(head) {
+            continue;
+        }
+        // Food being digested, such as under a ghost's head passing over
+        // its own body, cannot be eaten again
+        let Some(f_idx) = state
+            .food
+            .iter()
+            .position(|f| f.pos == head && !f.digesting)
+        else {
+            continue;
+        };
+        // Eaten food stays under the body until digested, and never expires
+        let food = &mut state.food[f_idx];
+        let kind = food.kind;
+        food.ttl = None;
+        food.digesting = true;
+        let snake = &mut state.snakes[i];
+        snake.score = snake.score.saturating_add(kind.score());
+        state.eaten = state.eaten.saturating_add(1);
+        state.events.push(GameEvent::Ate {
+            snake: i,
+            at: head,
+            kind,
+        });
+        // Food that does not grow the snake is gone once eaten
+        if !kind.grows() {
+            state.remove_food(f_idx);
+        }
+        match kind {
+            FoodKind::Shrink => shrink(state, i),
+            FoodKind::SpeedUp => state.pace += 1,
+            FoodKind::SlowDown => state.pace -= 1,
+            _ => {}
+        }
+        if let Some(power) = kind.power() {
+            power_up(state, i, power, field.food_rules.power_ticks);
+        }
+        room = room && spawn_food(state, field, rng);
+    }
+
+    // Food left uneaten for too long disappears, and new food takes its
+    // place
+    for f_idx in (0..state.food.len()).rev() {
+        let food = &mut state.food[f_idx];
+        match food.ttl {
+            Some(ttl) if ttl > 1 => food.ttl = Some(ttl - 1),
+            Some(_) => {
+                let food = state.remove_food(f_idx);
+                info!("Food at {:?} expired", food.pos);
+                state.events.push(GameEvent::FoodExpired { at: food.pos });
+                room = room && spawn_food(state, field, rng);
+            }
+            None => {}
+        }
+    }
+
+    if state.snakes.len() == 1 && state.snakes[0].body.len() >= field.floor() {
+        info!("Snake fills the field");
+        return GameResult::GameOver(Outcome::Win);
+    }
+
     GameResult::Continue
 }
 
+// Decide whether the game is over after this tick's `deaths`.
+fn decide(state: &GameState, deaths: &[(usize, Death)]) -> Option<Outcome> {
+    if state.snakes.len() == 1 {
+        return deaths.first().map(|&(_, death)| Outcome::Died(death));
+    }
+    let mut alive = (0..state.snakes.len()).filter(|&i| state.snakes[i].alive);
+    match (alive.next(), alive.next()) {
+        (None, _) => Some(Outcome::Draw),
+        (Some(winner), None) => Some(Outcome::Winner(winner)),
+        _ => None,
+    }
+}
+
 // Determine the new direction based on the key event and previous direction.
-fn find_direction(event: &PlayerEvent, prev: (i16, i16)) -> (i16, i16) {
+// Turns of a snake that has not moved yet are taken as if it faced up.
+pub fn find_direction(event: &PlayerEvent, prev: (i16, i16)) -> (i16, i16) {
+    let facing = if prev == (0, 0) { (0, -1) } else { prev };
     let delta = match event {
         PlayerEvent::Up => (0, -1),
         PlayerEvent::Down => (0, 1),
         PlayerEvent::Left => (-1, 0),
         PlayerEvent::Right => (1, 0),
+        // With y growing downwards
+        PlayerEvent::TurnLeft => (facing.1, -facing.0),
+        PlayerEvent::TurnRight => (-facing.1, facing.0),
         _ => prev,
     };
     if prev.0 + delta.0 == 0 && prev.1 + delta.1 == 0 {
@@ -140,81 +734,402 @@ fn find_direction(event: &PlayerEvent, prev: (i16, i16)) -> (i16, i16) {
     }
 }
 
-// Check if the snake has collided with walls or itself.
-fn collided(snake: &[(u16, u16)], field: &Field) -> bool {
-    let head = match snake.first() {
-        Some(&pos) => pos,
-        None => return false, // Snake is empty
-    };
+// Check if snake `idx` has collided with walls, obstacles, itself or
+// another living snake.
+//
+// Only a cell holding more than one segment can be a collision, so bodies
+// are searched just for the snakes that actually collided. The exception are
+// snakes of length one, whose tail before the move in `past_tails` is where
+// their head was: two of them swapping cells meet head-on.
+fn collided(
+    state: &GameState,
+    idx: usize,
+    field: &Field,
+    past_tails: &[(u16, u16)],
+) -> Option<Death> {
+    let snake = &state.snakes[idx];
+    let head = snake.head()?;
 
-    if head.0 < field.x_min
-        || head.0 > field.x_max
-        || head.1 < field.y_min
-        || head.1 > field.y_max
-    {
-        // Collided with wall
+    if !field.contains(head) || field.is_wall(head) {
         info!("Collided with wall at position {:?}", head);
-        return true;
+        return Some(Death::Wall(head));
+    }
+
+    let shared = state.grid.segments(head) > 1;
+    let ghost = state.has_power(idx, PowerUp::Ghost);
+    if shared && !ghost && snake.body.iter().skip(1).any(|pos| *pos == head) {
+        info!("Collided with itself {:?}", head);
+        return Some(Death::SelfBite(head));
     }
 
-    if snake.len() > 1 {
-        for s in snake[1..].iter() {
-            if head == *s {
-                // Collided with itself
-                info!("Collided with itself {:?}", head);
-                return true;
+    for (i, other) in state.snakes.iter().enumerate() {
+        if i == idx || !other.alive {
+            continue;
+        }
+        let swapped = snake.body.len() == 1
+            && other.body.len() == 1
+            && head == past_tails[i]
+            && other.head() == Some(past_tails[idx]);
+        if other.head() == Some(head) || swapped {
+            if other.body.len() >= snake.body.len() {
+                info!("Collided head-on with snake {} at {:?}", i, head);
+                return Some(Death::HeadOn { at: head, other: i });
             }
+        } else if shared && other.body.contains(&head) {
+            info!("Collided with snake {} at {:?}", i, head);
+            return Some(Death::Snake { at: head, other: i });
         }
     }
 
-    false
+    None
 }
 
-// Move the snake by `(dx, dy)`.
-fn locomote(snake: &mut Vec<(u16, u16)>, dx: i16, dy: i16) {
+// Move the snake by `(dx, dy)` according to the field's boundary policy.
+fn locomote(snake: &mut VecDeque<(u16, u16)>, field: &Field, dx: i16, dy: i16) {
     if snake.is_empty() {
-        snake.push((0, 0));
+        snake.push_back((0, 0));
     }
 
-    let (x, y) = snake[0];
+    let head = snake[0];
 
-    snake.remove(snake.len() - 1);
+    snake.pop_back();
 
-    let new_x = ((x as i16) + dx).max(0) as u16;
-    let new_y = ((y as i16) + dy).max(0) as u16;
+    snake.push_front(field.advance(head, dx, dy));
+}
 
-    snake.insert(0, (new_x, new_y));
+// Cut up to `SHRINK_SEGMENTS` segments off the tail of snake `idx`, along
+// with any food they were still digesting.
+fn shrink(state: &mut GameState, idx: usize) {
+    for _ in 0..SHRINK_SEGMENTS {
+        let body = &mut state.snakes[idx].body;
+        if body.len() <= 1 {
+            break;
+        }
+        let Some(tail) = body.pop_back() else {
+            break;
+        };
+        state.grid.remove_segment(tail);
+        if state.grid.segments(tail) == 0
+            && let Some(f_idx) = state.food.iter().position(|f| f.pos == tail)
+        {
+            state.remove_food(f_idx);
+        }
+        state.events.push(GameEvent::Shrank {
+            snake: idx,
+            vacated: tail,
+        });
+    }
 }
 
-// Generate a random position within given bounds.
-fn random_position<'a>(
-    randomizer: &'a RandomRange,
-    x_min: u16,
-    x_max: u16,
-    y_min: u16,
-    y_max: u16,
-) -> impl Fn() -> (u16, u16) + 'a {
-    move || {
-        let x = randomizer(x_min, x_max);
-        let y = randomizer(y_min, y_max);
-        (x, y)
+// Move each piece of food on the field one cell toward the head of snake
+// `idx`, along the axis it is further away on. Food stays put when the cell
+// is taken or holds food, unless it is the head itself, which then eats it.
+// As the head eats only one piece of food per tick, just one is pulled onto
+// it.
+fn attract(state: &mut GameState, idx: usize) {
+    let Some(head) = state.snakes[idx].head() else {
+        return;
+    };
+    for f_idx in 0..state.food.len() {
+        let from = state.food[f_idx].pos;
+        if state.grid.segments(from) > 0 || from == head {
+            continue;
+        }
+        let (dx, dy) = (head.0.abs_diff(from.0), head.1.abs_diff(from.1));
+        let to = if dx >= dy {
+            (
+                if head.0 > from.0 {
+                    from.0 + 1
+                } else {
+                    from.0 - 1
+                },
+                from.1,
+            )
+        } else {
+            (
+                from.0,
+                if head.1 > from.1 {
+                    from.1 + 1
+                } else {
+                    from.1 - 1
+                },
+            )
+        };
+        if state.grid.has_food(to) || (to != head && !state.grid.is_open(to)) {
+            continue;
+        }
+        state.food[f_idx].pos = to;
+        state.grid.remove_food(from);
+        state.grid.add_food(to);
+        state.events.push(GameEvent::FoodMoved { from, to });
     }
 }
 
-// Generate a random values excluding certain values.
-//
-// # Arguments
+// Give snake `idx` `power` for `ticks` ticks, starting over if it already
+// has it.
+fn power_up(state: &mut GameState, idx: usize, power: PowerUp, ticks: u32) {
+    info!("Snake {} picked up {:?}", idx, power);
+    match state
+        .effects
+        .iter_mut()
+        .find(|effect| effect.snake == idx && effect.power == power)
+    {
+        Some(effect) => effect.ticks = ticks,
+        None => state.effects.push(Effect {
+            snake: idx,
+            power,
+            ticks,
+        }),
+    }
+    state.events.push(GameEvent::PoweredUp {
+        snake: idx,
+        power,
+        ticks,
+    });
+}
+
+// Place food of a kind picked by the field's food rules on a random free
+// cell. Returns false, and reports `GameEvent::NoSpace`, if no cell is free.
 //
-// * `randomizer` - a function that generates random values
-// * `exclude` - values to exclude
-fn random_exclude<T: PartialEq>(
-    randomizer: &impl Fn() -> T,
-    exclude: &[T],
-) -> T {
-    loop {
-        let v = randomizer();
-        if !exclude.contains(&v) {
-            return v;
+// Draws from the grid's index of free cells, so it takes constant time
+// however full the field is.
+fn spawn_food(
+    state: &mut GameState,
+    field: &Field,
+    rng: &mut dyn Random,
+) -> bool {
+    match state.grid.random_free(rng) {
+        Some(pos) => {
+            let food = field.food_rules.spawn(pos, rng);
+            state.add_food(food);
+            info!("Spawned {:?} food at {:?}", food.kind, pos);
+            true
+        }
+        None => {
+            info!("No space left for food");
+            state.events.push(GameEvent::NoSpace);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_no_space(state: &GameState) -> bool {
+        state.events().contains(&GameEvent::NoSpace)
+    }
+
+    type Body<'a> = &'a [(u16, u16)];
+
+    // A game of snakes with the given bodies, head first, and directions.
+    // `play` builds the grid on its first call.
+    fn with_snakes(snakes: &[(Body, (i16, i16))]) -> GameState {
+        let mut state = GameState::with_players(snakes.len());
+        for (snake, (body, direction)) in state.snakes.iter_mut().zip(snakes) {
+            snake.body = body.iter().copied().collect();
+            snake.direction = *direction;
+        }
+        state
+    }
+
+    #[test]
+    fn head_on_kills_the_shorter_snake_or_both() {
+        let field = Field::new(7, 3);
+        let mut rng = SeededRng::new(0);
+        let idle = [PlayerEvent::Idle; 2];
+        let mut state =
+            with_snakes(&[(&[(2, 2), (1, 2)], (1, 0)), (&[(4, 2)], (-1, 0))]);
+        let result = play(&mut state, &field, &idle, &mut rng);
+        assert_eq!(result, GameResult::GameOver(Outcome::Winner(0)));
+        let death = Death::HeadOn {
+            at: (3, 2),
+            other: 0,
+        };
+        assert_eq!(state.snakes[1].death, Some(death));
+
+        let mut state = with_snakes(&[
+            (&[(2, 2), (1, 2)], (1, 0)),
+            (&[(4, 2), (5, 2)], (-1, 0)),
+        ]);
+        let result = play(&mut state, &field, &idle, &mut rng);
+        assert_eq!(result, GameResult::GameOver(Outcome::Draw));
+        assert!(state.snakes.iter().all(|snake| !snake.alive));
+    }
+
+    #[test]
+    fn running_into_a_body_kills_the_snake() {
+        let field = Field::new(7, 3);
+        let mut rng = SeededRng::new(0);
+        let mut state = with_snakes(&[
+            (&[(2, 1)], (0, 1)),
+            (&[(3, 2), (2, 2), (1, 2)], (1, 0)),
+        ]);
+        let result = play(&mut state, &field, &[PlayerEvent::Idle], &mut rng);
+        assert_eq!(result, GameResult::GameOver(Outcome::Winner(1)));
+        let death = Death::Snake {
+            at: (2, 2),
+            other: 1,
+        };
+        assert_eq!(state.snakes[0].death, Some(death));
+    }
+
+    #[test]
+    fn heads_may_enter_cells_that_tails_leave() {
+        let field = Field::new(7, 3);
+        let mut rng = SeededRng::new(0);
+        let mut state = with_snakes(&[
+            (&[(1, 1), (2, 1), (2, 2), (1, 2)], (-1, 0)),
+            (&[(5, 1)], (1, 0)),
+            (&[(4, 3), (4, 2), (5, 2)], (0, 1)),
+        ]);
+        // Snake 1 chases its own tail, snake 2 follows snake 3's
+        let events = [PlayerEvent::Down, PlayerEvent::Down, PlayerEvent::Left];
+        let result = play(&mut state, &field, &events, &mut rng);
+        assert_eq!(result, GameResult::Continue);
+        assert!(state.snakes.iter().all(|snake| snake.alive));
+        assert_eq!(state.snakes[0].head(), Some((1, 2)));
+        assert_eq!(state.snakes[1].head(), Some((5, 2)));
+        assert_eq!(state.snakes[2].head(), Some((3, 3)));
+    }
+
+    #[test]
+    fn chasing_the_tail_over_eaten_food_grows_without_eating_again() {
+        let field = Field::new(3, 3);
+        let mut rng = SeededRng::new(0);
+        let mut state =
+            with_snakes(&[(&[(1, 1), (2, 1), (2, 2), (1, 2)], (-1, 0))]);
+        state.food.push(Food {
+            digesting: true,
+            ..Food::new((1, 2))
+        });
+        let result = play(&mut state, &field, &[PlayerEvent::Down], &mut rng);
+        assert_eq!(result, GameResult::Continue);
+        let snake = &state.snakes[0];
+        assert_eq!(snake.body, [(1, 2), (1, 1), (2, 1), (2, 2), (1, 2)]);
+        assert_eq!(snake.score, 0);
+        assert_eq!(state.eaten, 0);
+        assert!(state.food.is_empty());
+        let grew = GameEvent::Grew {
+            snake: 0,
+            at: (1, 2),
+        };
+        assert_eq!(state.events().last(), Some(&grew));
+    }
+
+    #[test]
+    fn single_cell_field_is_won_without_food() {
+        let field = Field::new(1, 1);
+        let mut state = GameState::new();
+        let mut rng = SeededRng::new(0);
+        let result = play(&mut state, &field, &[PlayerEvent::Idle], &mut rng);
+        assert_eq!(result, GameResult::GameOver(Outcome::Win));
+        assert!(state.food.is_empty());
+        assert!(has_no_space(&state));
+    }
+
+    #[test]
+    fn two_cell_field_is_won_after_one_food() {
+        let field = Field::new(2, 1).with_boundary(Boundary::Wrap);
+        let mut state = GameState::new();
+        let mut rng = SeededRng::new(0);
+        let left = [PlayerEvent::Left];
+        let result = play(&mut state, &field, &[PlayerEvent::Idle], &mut rng);
+        assert_eq!(result, GameResult::Continue);
+        assert_eq!(state.snakes[0].body, [(2, 1)]);
+        assert_eq!(state.food, [Food::new((1, 1))]);
+
+        // The tail leaves the only other cell for the next food
+        let result = play(&mut state, &field, &left, &mut rng);
+        assert_eq!(result, GameResult::Continue);
+        assert_eq!(state.snakes[0].score, 1);
+        assert_eq!(state.food.last().map(|food| food.pos), Some((2, 1)));
+
+        // Wrapping onto that food digests the first, filling the field
+        let result = play(&mut state, &field, &left, &mut rng);
+        assert_eq!(result, GameResult::GameOver(Outcome::Win));
+        assert_eq!(state.snakes[0].score, 2);
+        assert!(has_no_space(&state));
+    }
+
+    #[test]
+    fn speed_goes_by_food_eaten_rather_than_score() {
+        let speed = "steps:start=200,every=1,step=10".parse().unwrap();
+        let field = Field::new(5, 1).with_speed(speed);
+        let mut state = GameState::new();
+        let mut rng = SeededRng::new(0);
+        play(&mut state, &field, &[PlayerEvent::Idle], &mut rng);
+        state.remove_food(0);
+        state.add_food(Food {
+            pos: (2, 1),
+            kind: FoodKind::Bonus,
+            ttl: None,
+            digesting: false,
+        });
+        play(&mut state, &field, &[PlayerEvent::Left], &mut rng);
+        assert_eq!(state.snakes[0].score, BONUS_SCORE);
+        assert_eq!(state.eaten, 1);
+        assert_eq!(state.tick_millis(&field), 190);
+    }
+
+    #[test]
+    fn power_ups_end_when_their_snake_dies() {
+        let field = Field::new(7, 3);
+        let mut state = GameState::with_players(3);
+        let mut rng = SeededRng::new(0);
+        let events = [PlayerEvent::Idle, PlayerEvent::Idle, PlayerEvent::Right];
+        play(&mut state, &field, &events, &mut rng);
+        assert_eq!(state.snakes[2].head(), Some((7, 2)));
+        for power in [PowerUp::SlowMotion, PowerUp::Magnet] {
+            power_up(&mut state, 2, power, POWER_TICKS);
+        }
+        power_up(&mut state, 0, PowerUp::Ghost, POWER_TICKS);
+        let slow = state.tick_millis(&field);
+
+        let result = play(&mut state, &field, &events, &mut rng);
+        assert_eq!(result, GameResult::Continue);
+        assert!(!state.snakes[2].alive);
+        assert_eq!(state.effects.len(), 1);
+        assert_eq!(state.effects[0].power, PowerUp::Ghost);
+        assert_eq!(state.tick_millis(&field), slow / 2);
+        let ended = state.events().iter().filter(|event| {
+            matches!(event, GameEvent::PowerEnded { snake: 2, .. })
+        });
+        assert_eq!(ended.count(), 2);
+    }
+
+    #[test]
+    fn magnets_pull_one_food_onto_the_head() {
+        let field = Field::new(5, 5);
+        let mut rng = SeededRng::new(0);
+        let mut state = with_snakes(&[(&[(2, 2)], (1, 0))]);
+        state.food = vec![Food::new((4, 2)), Food::new((3, 3))];
+        power_up(&mut state, 0, PowerUp::Magnet, POWER_TICKS);
+
+        let result = play(&mut state, &field, &[PlayerEvent::Idle], &mut rng);
+        assert_eq!(result, GameResult::Continue);
+        assert_eq!(state.snakes[0].score, 1);
+        assert_eq!(state.eaten, 1);
+        let on_head = state.food.iter().filter(|food| food.pos == (3, 2));
+        assert_eq!(on_head.count(), 1);
+        assert!(state.food.contains(&Food::new((3, 3))));
+    }
+
+    #[test]
+    fn food_spawns_on_the_last_free_cell() {
+        let field = Field::new(3, 1);
+        let mut state = GameState::new();
+        state.snakes[0].body = [(1, 1), (2, 1)].into_iter().collect();
+        state.rebuild_grid(&field);
+        for seed in 0..10 {
+            let mut full = state.clone();
+            let mut rng = SeededRng::new(seed);
+            assert!(spawn_food(&mut full, &field, &mut rng));
+            assert_eq!(full.food, [Food::new((3, 1))]);
+            assert!(!spawn_food(&mut full, &field, &mut rng));
+            assert!(has_no_space(&full));
+            assert_eq!(full.food.len(), 1);
         }
     }
 }
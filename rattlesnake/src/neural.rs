@@ -0,0 +1,233 @@
+//! Small feed-forward networks that play snake.
+//!
+//! A network sees `INPUTS` values: whether moving up, down, left or right
+//! runs into a wall or body, whether the nearest food lies in each of those
+//! directions, and the snake's current direction. It answers with one value
+//! per move and the snake takes the highest. Networks are evolved by the
+//! `snake-evolve` crate and stored as versioned JSON genomes.
+use crate::{Controller, Field, GameState, PlayerEvent, SeededRng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Version of the genome file format written by this crate.
+pub const GENOME_VERSION: u32 = 1;
+pub const INPUTS: usize = 12;
+pub const OUTPUTS: usize = 4;
+
+const MOVES: [(PlayerEvent, (i16, i16)); OUTPUTS] = [
+    (PlayerEvent::Up, (0, -1)),
+    (PlayerEvent::Down, (0, 1)),
+    (PlayerEvent::Left, (-1, 0)),
+    (PlayerEvent::Right, (1, 0)),
+];
+
+// Weights of a network with `tanh` hidden layers and a linear output layer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    version: u32,
+    // Number of neurons per layer, from `INPUTS` to `OUTPUTS`.
+    layers: Vec<usize>,
+    // Per layer after the first, the weights of each neuron followed by its
+    // bias.
+    weights: Vec<f32>,
+}
+
+impl Genome {
+    // A network with hidden layers of the given sizes and random weights.
+    pub fn random(hidden: &[usize], rng: &mut SeededRng) -> Self {
+        let mut layers = vec![INPUTS];
+        layers.extend(hidden);
+        layers.push(OUTPUTS);
+        let len = layers.windows(2).map(|w| (w[0] + 1) * w[1]).sum();
+        Genome {
+            version: GENOME_VERSION,
+            layers,
+            weights: (0..len).map(|_| uniform(rng)).collect(),
+        }
+    }
+
+    pub fn layers(&self) -> &[usize] {
+        &self.layers
+    }
+
+    // Nudge each weight with probability `rate` by up to `strength`.
+    pub fn mutate(&mut self, rate: f32, strength: f32, rng: &mut SeededRng) {
+        for weight in &mut self.weights {
+            if (uniform(rng) + 1.0) / 2.0 < rate {
+                *weight += uniform(rng) * strength;
+            }
+        }
+    }
+
+    // A child taking each weight from either parent, which must have the
+    // same layers.
+    pub fn crossover(&self, other: &Genome, rng: &mut SeededRng) -> Genome {
+        let weights = self
+            .weights
+            .iter()
+            .zip(&other.weights)
+            .map(|(a, b)| if uniform(rng) < 0.0 { *a } else { *b })
+            .collect();
+        Genome {
+            version: GENOME_VERSION,
+            layers: self.layers.clone(),
+            weights,
+        }
+    }
+
+    // Run the network on `inputs`.
+    pub fn outputs(&self, inputs: &[f32; INPUTS]) -> [f32; OUTPUTS] {
+        let mut values = inputs.to_vec();
+        let mut weights = self.weights.iter();
+        let last = self.layers.len() - 1;
+        for (i, size) in self.layers.iter().enumerate().skip(1) {
+            values = (0..*size)
+                .map(|_| {
+                    let sum: f32 = values
+                        .iter()
+                        .map(|value| value * weights.next().unwrap_or(&0.0))
+                        .sum();
+                    let sum = sum + weights.next().unwrap_or(&0.0);
+                    if i == last { sum } else { sum.tanh() }
+                })
+                .collect();
+        }
+        let mut outputs = [0.0; OUTPUTS];
+        outputs.copy_from_slice(&values);
+        outputs
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("genome is serializable")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, GenomeError> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        let version = value
+            .get("version")
+            .and_then(|version| version.as_u64())
+            .ok_or(GenomeError::MissingVersion)?;
+        if version != GENOME_VERSION as u64 {
+            return Err(GenomeError::UnsupportedVersion(version));
+        }
+        let genome: Genome = serde_json::from_value(value)?;
+        let len: usize =
+            genome.layers.windows(2).map(|w| (w[0] + 1) * w[1]).sum();
+        if genome.layers.first() != Some(&INPUTS)
+            || genome.layers.last() != Some(&OUTPUTS)
+            || genome.weights.len() != len
+        {
+            return Err(GenomeError::Shape);
+        }
+        Ok(genome)
+    }
+}
+
+#[derive(Debug)]
+pub enum GenomeError {
+    // The file is not a valid genome.
+    Parse(serde_json::Error),
+    // The file does not state its format version.
+    MissingVersion,
+    // The file was written in a format version this crate cannot read.
+    UnsupportedVersion(u64),
+    // The layers do not match the inputs and outputs, or the weights.
+    Shape,
+}
+
+impl fmt::Display for GenomeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenomeError::Parse(err) => write!(f, "invalid genome: {err}"),
+            GenomeError::MissingVersion => write!(f, "genome has no version"),
+            GenomeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported genome version {version}")
+            }
+            GenomeError::Shape => {
+                write!(f, "genome layers do not match its weights")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenomeError {}
+
+impl From<serde_json::Error> for GenomeError {
+    fn from(err: serde_json::Error) -> Self {
+        GenomeError::Parse(err)
+    }
+}
+
+// Plays the moves a genome's network picks.
+#[derive(Debug, Clone)]
+pub struct NeuralBot {
+    genome: Genome,
+}
+
+impl NeuralBot {
+    pub fn new(genome: Genome) -> Self {
+        NeuralBot { genome }
+    }
+
+    // What the network of snake `snake` sees, see the module docs.
+    pub fn inputs(
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> [f32; INPUTS] {
+        let mut inputs = [0.0; INPUTS];
+        let player = &state.snakes[snake];
+        let Some(head) = player.head() else {
+            return inputs;
+        };
+        for (i, (_, (dx, dy))) in MOVES.iter().enumerate() {
+            let pos = field.advance(head, *dx, *dy);
+            let blocked = !field.contains(pos)
+                || field.is_wall(pos)
+                || state.alive().any(|snake| {
+                    // Tails move on before heads arrive
+                    let len = snake.body.len().saturating_sub(1).max(1);
+                    snake.body.iter().take(len).any(|p| *p == pos)
+                });
+            inputs[i] = blocked as u8 as f32;
+        }
+        // Food under a body has been eaten already
+        let food = state
+            .food
+            .iter()
+            .map(|f| f.pos)
+            .filter(|f| !state.alive().any(|snake| snake.body.contains(f)))
+            .min_by_key(|f| {
+                head.0.abs_diff(f.0) as u32 + head.1.abs_diff(f.1) as u32
+            });
+        if let Some(food) = food {
+            inputs[4] = (food.1 < head.1) as u8 as f32;
+            inputs[5] = (food.1 > head.1) as u8 as f32;
+            inputs[6] = (food.0 < head.0) as u8 as f32;
+            inputs[7] = (food.0 > head.0) as u8 as f32;
+        }
+        for (i, (_, delta)) in MOVES.iter().enumerate() {
+            inputs[8 + i] = (player.direction == *delta) as u8 as f32;
+        }
+        inputs
+    }
+}
+
+impl Controller for NeuralBot {
+    fn next_event(
+        &mut self,
+        state: &GameState,
+        field: &Field,
+        snake: usize,
+    ) -> PlayerEvent {
+        let outputs = self.genome.outputs(&Self::inputs(state, field, snake));
+        let best =
+            (0..OUTPUTS).max_by(|a, b| outputs[*a].total_cmp(&outputs[*b]));
+        best.map_or(PlayerEvent::Idle, |i| MOVES[i].0)
+    }
+}
+
+// A random value in `-1.0..1.0`.
+fn uniform(rng: &mut SeededRng) -> f32 {
+    (rng.next_u64() >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
@@ -0,0 +1,62 @@
+//! How snakes die and games end.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Why a snake died, with the position its head moved to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Death {
+    // Hit the field border or an interior wall.
+    Wall((u16, u16)),
+    // Ran into its own body.
+    SelfBite((u16, u16)),
+    // Ran into the body of snake `other`.
+    Snake { at: (u16, u16), other: usize },
+    // Met the head of snake `other` without being longer.
+    HeadOn { at: (u16, u16), other: usize },
+}
+
+// How a game ended.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Outcome {
+    // The snake of a single-player game died.
+    Died(Death),
+    // A player quit.
+    Quit,
+    // The snake of a single-player game fills the field.
+    Win,
+    // The given snake outlived all others in a multiplayer game.
+    Winner(usize),
+    // The last snakes of a multiplayer game died in the same tick.
+    Draw,
+}
+
+impl fmt::Display for Death {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Death::Wall(at) => write!(f, "hit a wall at {at:?}"),
+            Death::SelfBite(at) => write!(f, "bit itself at {at:?}"),
+            Death::Snake { at, other } => {
+                write!(f, "ran into snake {} at {at:?}", other + 1)
+            }
+            Death::HeadOn { at, other } => {
+                write!(
+                    f,
+                    "lost a head-on collision with snake {} at {at:?}",
+                    other + 1
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Died(death) => write!(f, "The snake {death}"),
+            Outcome::Quit => write!(f, "Game quit"),
+            Outcome::Win => write!(f, "The snake fills the field"),
+            Outcome::Winner(snake) => write!(f, "Snake {} wins", snake + 1),
+            Outcome::Draw => write!(f, "Draw"),
+        }
+    }
+}
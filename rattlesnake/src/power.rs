@@ -0,0 +1,39 @@
+//! Power-ups that change the rules for a while.
+//!
+//! A snake picks up a power-up by eating food of its kind, see `FoodKind`.
+//! The effect then lasts for the field's `power_ticks` ticks; picking up the
+//! same power-up again starts the count over.
+use serde::{Deserialize, Serialize};
+
+// Ticks an effect lasts unless the food rules say otherwise.
+pub const POWER_TICKS: u32 = 30;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum PowerUp {
+    // The snake passes through its own body.
+    Ghost,
+    // Food moves one cell toward the snake's head every tick.
+    Magnet,
+    // Ticks take twice as long, for every snake.
+    SlowMotion,
+}
+
+impl PowerUp {
+    // Name of the power-up in food rules and frontends.
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUp::Ghost => "ghost",
+            PowerUp::Magnet => "magnet",
+            PowerUp::SlowMotion => "slow-motion",
+        }
+    }
+}
+
+// A power-up in effect for a snake.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub struct Effect {
+    pub snake: usize,
+    pub power: PowerUp,
+    // Ticks left, counting the next one.
+    pub ticks: u32,
+}
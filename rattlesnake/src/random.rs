@@ -0,0 +1,46 @@
+//! Sources of randomness.
+use serde::{Deserialize, Serialize};
+
+// Source of random values for the game.
+pub trait Random {
+    // Generate a random index into a collection of `len` items.
+    fn index(&mut self, len: usize) -> usize;
+}
+
+// Portable pseudo random number generator.
+//
+// Implements SplitMix64, which only relies on wrapping integer arithmetic,
+// so the same seed yields the same sequence on every target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+
+    // Generate a random 64-bit value, e.g. to seed further generators.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Split off a generator with a sequence of its own, e.g. for a bot to
+    // simulate games without touching the food of the real one. This
+    // generator moves on by one value.
+    pub fn fork(&mut self) -> SeededRng {
+        SeededRng::new(self.next_u64())
+    }
+}
+
+impl Random for SeededRng {
+    fn index(&mut self, len: usize) -> usize {
+        // Scale instead of taking a modulo to avoid bias towards low values
+        ((self.next_u64() as u128 * len as u128) >> 64) as usize
+    }
+}
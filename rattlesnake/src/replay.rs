@@ -0,0 +1,194 @@
+//! Recording and playback of games.
+//!
+//! A game is fully determined by its field, the number of players, the seed
+//! of its `SeededRng` and the events passed to `play` on every tick. A
+//! `Replay` records exactly that and a `ReplayPlayer` re-drives `play` with
+//! it.
+use crate::{Field, GameResult, GameState, PlayerEvent, SeededRng, play};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Version of the replay file format written by this crate.
+//
+// Replays only reproduce a game with the engine that recorded it, so the
+// version is bumped whenever `play` consumes randomness differently.
+pub const REPLAY_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    version: u32,
+    seed: u64,
+    players: usize,
+    field: Field,
+    ticks: Vec<Vec<PlayerEvent>>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, field: &Field, players: usize) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            players,
+            field: field.clone(),
+            ticks: Vec::new(),
+        }
+    }
+
+    // Record the events passed to `play` for the next tick.
+    pub fn record(&mut self, events: &[PlayerEvent]) {
+        self.ticks.push(events.to_vec());
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn field(&self) -> &Field {
+        &self.field
+    }
+
+    // Number of recorded ticks.
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("replay is serializable")
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, ReplayError> {
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        let version = value
+            .get("version")
+            .and_then(|version| version.as_u64())
+            .ok_or(ReplayError::MissingVersion)?;
+        if version != REPLAY_VERSION as u64 {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    // The file is not a valid replay.
+    Parse(serde_json::Error),
+    // The file does not state its format version.
+    MissingVersion,
+    // The file was written in a format version this crate cannot read.
+    UnsupportedVersion(u64),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Parse(err) => write!(f, "invalid replay: {err}"),
+            ReplayError::MissingVersion => write!(f, "replay has no version"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "unsupported replay version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(err: serde_json::Error) -> Self {
+        ReplayError::Parse(err)
+    }
+}
+
+// Re-drives `play` tick by tick from a replay.
+pub struct ReplayPlayer {
+    replay: Replay,
+    state: GameState,
+    rng: SeededRng,
+    tick: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        ReplayPlayer {
+            state: GameState::with_players(replay.players),
+            rng: SeededRng::new(replay.seed),
+            replay,
+            tick: 0,
+        }
+    }
+
+    // Play the next recorded tick, or return `None` at the end of the replay.
+    pub fn step(&mut self) -> Option<GameResult> {
+        let events = self.replay.ticks.get(self.tick)?;
+        self.tick += 1;
+        Some(play(
+            &mut self.state,
+            &self.replay.field,
+            events,
+            &mut self.rng,
+        ))
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn field(&self) -> &Field {
+        &self.replay.field
+    }
+
+    // Number of ticks played so far.
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FoodKind, FoodRules, bot};
+
+    #[test]
+    fn replaying_a_recorded_game_reproduces_it() {
+        let rules = FoodRules::default()
+            .with_weight(FoodKind::Bonus, 1)
+            .with_weight(FoodKind::Shrink, 1);
+        let field = Field::new(10, 8).with_food_rules(rules);
+        let seed = 42;
+        let mut replay = Replay::new(seed, &field, 2);
+        let mut state = GameState::with_players(2);
+        let mut rng = SeededRng::new(seed);
+        let mut bots = [bot("greedy").unwrap(), bot("pathfinder").unwrap()];
+        let mut results = Vec::new();
+        for _ in 0..200 {
+            let events: Vec<PlayerEvent> = (0..2)
+                .map(|i| bots[i].next_event(&state, &field, i))
+                .collect();
+            replay.record(&events);
+            let result = play(&mut state, &field, &events, &mut rng);
+            results.push(result);
+            if result != GameResult::Continue {
+                break;
+            }
+        }
+        assert!(state.eaten > 1);
+
+        let replay = Replay::from_json(&replay.to_json()).unwrap();
+        let mut player = ReplayPlayer::new(replay);
+        let replayed: Vec<GameResult> =
+            std::iter::from_fn(|| player.step()).collect();
+        assert!(player.is_finished());
+        assert_eq!(replayed, results);
+        let json = |state: &GameState| serde_json::to_value(state).unwrap();
+        assert_eq!(json(player.state()), json(&state));
+        assert_eq!(player.state().events(), state.events());
+    }
+}
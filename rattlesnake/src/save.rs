@@ -0,0 +1,244 @@
+//! Saving and resuming games in progress.
+//!
+//! Saves are versioned JSON documents. Saves written by an older version of
+//! this crate are upgraded step by step through `MIGRATIONS` before they are
+//! read, so a format change only needs a migration from its predecessor.
+use crate::{Field, FoodKind, GameState, SeededRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+// Version of the save format written by this crate.
+pub const SAVE_VERSION: u32 = 4;
+
+type Migration = fn(Value) -> Result<Value, SaveError>;
+
+// `MIGRATIONS[i]` upgrades a save from version `i + 1` to `i + 2`.
+const MIGRATIONS: &[Migration] = &[typed_food, food_eaten, digesting_food];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedGame {
+    version: u32,
+    pub field: Field,
+    pub state: GameState,
+    pub rng: SeededRng,
+}
+
+impl SavedGame {
+    pub fn new(field: &Field, state: &GameState, rng: &SeededRng) -> Self {
+        SavedGame {
+            version: SAVE_VERSION,
+            field: field.clone(),
+            state: state.clone(),
+            rng: rng.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("save is serializable")
+    }
+
+    // Read a save, upgrading it from older versions if needed.
+    pub fn from_json(text: &str) -> Result<Self, SaveError> {
+        let value: Value = serde_json::from_str(text)?;
+        Ok(serde_json::from_value(migrate(value)?)?)
+    }
+}
+
+// Upgrade a save to `SAVE_VERSION`.
+fn migrate(mut value: Value) -> Result<Value, SaveError> {
+    let mut version = read_version(&value)?;
+    if version == 0 || version > SAVE_VERSION as u64 {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    while version < SAVE_VERSION as u64 {
+        value = MIGRATIONS[version as usize - 1](value)?;
+        version += 1;
+        value["version"] = version.into();
+    }
+    Ok(value)
+}
+
+// Version 2 gave food a kind: positions become normal food.
+fn typed_food(mut value: Value) -> Result<Value, SaveError> {
+    let invalid = |reason: &str| SaveError::Migration {
+        from: 1,
+        reason: reason.to_string(),
+    };
+    let food = value
+        .pointer_mut("/state/food")
+        .and_then(|food| food.as_array_mut())
+        .ok_or_else(|| invalid("state has no food"))?;
+    for item in food {
+        if !item.is_array() {
+            return Err(invalid("food is not a position"));
+        }
+        *item = serde_json::json!({ "pos": item.take(), "kind": "Normal" });
+    }
+    Ok(value)
+}
+
+// Version 3 counts the food eaten, which older saves only kept as scores.
+//
+// Version 2 saves may have scored bonus food and power-ups, so their scores
+// are turned back into food by the average score of the food the field's
+// rules spawn. That is exact for normal food only and an estimate otherwise.
+fn food_eaten(mut value: Value) -> Result<Value, SaveError> {
+    let snakes = value
+        .pointer("/state/snakes")
+        .and_then(|snakes| snakes.as_array())
+        .ok_or_else(|| SaveError::Migration {
+            from: 2,
+            reason: "state has no snakes".to_string(),
+        })?;
+    let score: u64 = snakes
+        .iter()
+        .filter_map(|snake| snake.get("score")?.as_u64())
+        .sum();
+    let weights = value
+        .pointer("/field/food_rules/weights")
+        .and_then(|weights| weights.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let (spawned, scored) = FoodKind::ALL.iter().zip(weights).fold(
+        (0, 0),
+        |(spawned, scored), (kind, weight)| {
+            let weight = weight.as_u64().unwrap_or_default();
+            (spawned + weight, scored + weight * kind.score() as u64)
+        },
+    );
+    let eaten = if spawned == 0 || scored == 0 {
+        score
+    } else {
+        (score * spawned + scored / 2) / scored
+    };
+    value["state"]["eaten"] = eaten.into();
+    Ok(value)
+}
+
+// Version 4 marks food that was eaten, which older saves only told apart by
+// a living body lying over it.
+fn digesting_food(mut value: Value) -> Result<Value, SaveError> {
+    let invalid = |reason: &str| SaveError::Migration {
+        from: 3,
+        reason: reason.to_string(),
+    };
+    let snakes = value
+        .pointer("/state/snakes")
+        .and_then(|snakes| snakes.as_array())
+        .ok_or_else(|| invalid("state has no snakes"))?;
+    let bodies: Vec<Value> = snakes
+        .iter()
+        .filter(|snake| snake.get("alive") == Some(&Value::Bool(true)))
+        .filter_map(|snake| snake.get("body")?.as_array())
+        .flatten()
+        .cloned()
+        .collect();
+    let food = value
+        .pointer_mut("/state/food")
+        .and_then(|food| food.as_array_mut())
+        .ok_or_else(|| invalid("state has no food"))?;
+    for item in food {
+        let pos = item.get("pos");
+        let digesting = pos.is_some_and(|pos| bodies.contains(pos));
+        item["digesting"] = digesting.into();
+    }
+    Ok(value)
+}
+
+fn read_version(value: &Value) -> Result<u64, SaveError> {
+    value
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .ok_or(SaveError::MissingVersion)
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    // The file is not a valid save.
+    Parse(serde_json::Error),
+    // The file does not state its format version.
+    MissingVersion,
+    // The file was written in a format version this crate cannot read.
+    UnsupportedVersion(u64),
+    // An older save could not be upgraded.
+    Migration { from: u64, reason: String },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Parse(err) => write!(f, "invalid save: {err}"),
+            SaveError::MissingVersion => write!(f, "save has no version"),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save version {version}")
+            }
+            SaveError::Migration { from, reason } => {
+                write!(f, "cannot upgrade save from version {from}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_saves_count_their_scores_as_food_eaten() {
+        let text = r#"{
+            "version": 1,
+            "field": {
+                "x_min": 1, "x_max": 5, "y_min": 1, "y_max": 5,
+                "boundary": "Walls", "walls": [], "starts": [], "food": []
+            },
+            "state": {
+                "snakes": [
+                    {"body": [[2, 2]], "direction": [1, 0], "score": 4,
+                     "alive": true},
+                    {"body": [[4, 4]], "direction": [0, 1], "score": 3,
+                     "alive": true}
+                ],
+                "food": [[3, 3]]
+            },
+            "rng": {"state": 5}
+        }"#;
+        let save = SavedGame::from_json(text).unwrap();
+        assert_eq!(save.state.eaten, 7);
+        assert_eq!(save.state.food, [crate::Food::new((3, 3))]);
+    }
+
+    #[test]
+    fn bonus_scores_count_as_fewer_food_eaten() {
+        // Two normal and one bonus food, by weights two to one
+        let text = r#"{
+            "version": 2,
+            "field": {
+                "x_min": 1, "x_max": 5, "y_min": 1, "y_max": 5,
+                "boundary": "Walls", "walls": [], "starts": [], "food": [],
+                "food_rules": {
+                    "weights": [2, 1, 0, 0, 0], "bonus_ticks": 50
+                }
+            },
+            "state": {
+                "snakes": [
+                    {"body": [[2, 2], [2, 3], [2, 4]], "direction": [0, -1],
+                     "score": 5, "alive": true}
+                ],
+                "food": [{"pos": [3, 3], "kind": "Normal"}]
+            },
+            "rng": {"state": 5}
+        }"#;
+        let save = SavedGame::from_json(text).unwrap();
+        assert_eq!(save.state.eaten, 3);
+        assert_eq!(save.state.snakes[0].score, 5);
+    }
+}
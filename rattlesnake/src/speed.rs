@@ -0,0 +1,166 @@
+//! How fast the game runs.
+//!
+//! A `Speed` curve gives the time between ticks by the food eaten so far.
+//! Curves are written as a name and optional settings, e.g. `constant`,
+//! `steps:every=5,step=20` or `ramp:start=300,min=60,foods=40`; settings
+//! left out keep their defaults. A plain number like `150` is a constant
+//! curve of that many milliseconds.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Time between ticks unless a curve says otherwise.
+pub const TICK_MILLIS: u64 = 250;
+// Shortest time between ticks that curves speed up to by default.
+const MIN_TICK_MILLIS: u64 = 80;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Speed {
+    // The same time between ticks all game.
+    Constant {
+        millis: u64,
+    },
+    // `step` milliseconds faster after every `every` foods, down to `min`.
+    Steps {
+        start: u64,
+        every: u32,
+        step: u64,
+        min: u64,
+    },
+    // Linearly faster from `start` to `min`, which is reached after `foods`
+    // foods.
+    Ramp {
+        start: u64,
+        min: u64,
+        foods: u32,
+    },
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::Constant {
+            millis: TICK_MILLIS,
+        }
+    }
+}
+
+impl Speed {
+    // Milliseconds between ticks once `eaten` foods have been eaten.
+    pub fn interval(&self, eaten: u32) -> u64 {
+        match *self {
+            Speed::Constant { millis } => millis,
+            Speed::Steps {
+                start,
+                every,
+                step,
+                min,
+            } => {
+                let steps = (eaten / every.max(1)) as u64;
+                start.saturating_sub(steps.saturating_mul(step)).max(min)
+            }
+            Speed::Ramp { start, min, foods } => {
+                let eaten = eaten.min(foods) as u64;
+                let faster =
+                    start.saturating_sub(min) * eaten / (foods.max(1) as u64);
+                (start - faster).max(min)
+            }
+        }
+    }
+}
+
+impl FromStr for Speed {
+    type Err = SpeedError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if let Ok(millis) = text.parse() {
+            return Ok(Speed::Constant { millis });
+        }
+        let (name, settings) = text.split_once(':').unwrap_or((text, ""));
+        let mut speed = match name {
+            "constant" => Speed::default(),
+            "steps" => Speed::Steps {
+                start: TICK_MILLIS,
+                every: 5,
+                step: 10,
+                min: MIN_TICK_MILLIS,
+            },
+            "ramp" => Speed::Ramp {
+                start: TICK_MILLIS,
+                min: MIN_TICK_MILLIS,
+                foods: 50,
+            },
+            _ => return Err(SpeedError::UnknownCurve(name.to_string())),
+        };
+        for setting in settings.split(',').filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| SpeedError::Invalid(setting.to_string()))?;
+            let value: u64 = value
+                .parse()
+                .map_err(|_| SpeedError::Invalid(setting.to_string()))?;
+            let count = u32::try_from(value)
+                .map_err(|_| SpeedError::Invalid(setting.to_string()));
+            match (&mut speed, key) {
+                (Speed::Constant { millis }, "millis")
+                | (Speed::Steps { start: millis, .. }, "start")
+                | (Speed::Ramp { start: millis, .. }, "start")
+                | (Speed::Steps { step: millis, .. }, "step")
+                | (Speed::Steps { min: millis, .. }, "min")
+                | (Speed::Ramp { min: millis, .. }, "min") => *millis = value,
+                (Speed::Steps { every: foods, .. }, "every")
+                | (Speed::Ramp { foods, .. }, "foods") => *foods = count?,
+                _ => return Err(SpeedError::UnknownSetting(key.to_string())),
+            }
+        }
+        Ok(speed)
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Speed::Constant { millis } => write!(f, "constant:millis={millis}"),
+            Speed::Steps {
+                start,
+                every,
+                step,
+                min,
+            } => write!(
+                f,
+                "steps:start={start},every={every},step={step},min={min}"
+            ),
+            Speed::Ramp { start, min, foods } => {
+                write!(f, "ramp:start={start},min={min},foods={foods}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SpeedError {
+    // The curve is not `constant`, `steps` or `ramp`.
+    UnknownCurve(String),
+    // The curve has no setting of that name.
+    UnknownSetting(String),
+    // A setting is not `name=number`.
+    Invalid(String),
+}
+
+impl fmt::Display for SpeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpeedError::UnknownCurve(name) => write!(
+                f,
+                "unknown speed curve {name:?}, choose constant, steps or ramp"
+            ),
+            SpeedError::UnknownSetting(key) => {
+                write!(f, "unknown speed setting {key:?}")
+            }
+            SpeedError::Invalid(setting) => {
+                write!(f, "invalid speed setting {setting:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpeedError {}
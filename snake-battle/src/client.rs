@@ -0,0 +1,121 @@
+//! Calling snakes over the Battlesnake API.
+use rattlesnake::battlesnake::{GameRequest, Move, MoveResponse, SnakeInfo};
+use std::fmt;
+use std::time::Duration;
+use ureq::Agent;
+
+#[derive(Debug)]
+pub enum RemoteError {
+    // The snake did not answer in time.
+    Timeout,
+    // The snake answered with an HTTP error status.
+    Status(u16),
+    // The snake could not be reached, e.g. because no server is running.
+    Http(String),
+    // The answer is not valid JSON for the request.
+    Invalid { body: String, reason: String },
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::Timeout => write!(f, "no answer in time"),
+            RemoteError::Status(status) => write!(f, "HTTP status {status}"),
+            RemoteError::Http(err) => write!(f, "{err}"),
+            RemoteError::Invalid { body, reason } => {
+                write!(f, "invalid answer {body:?}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RemoteError {}
+
+impl From<ureq::Error> for RemoteError {
+    fn from(err: ureq::Error) -> Self {
+        match err {
+            ureq::Error::Timeout(_) => RemoteError::Timeout,
+            ureq::Error::StatusCode(status) => RemoteError::Status(status),
+            err => RemoteError::Http(err.to_string()),
+        }
+    }
+}
+
+// A snake served at a base URL such as `http://localhost:8000`.
+pub struct RemoteSnake {
+    url: String,
+    agent: Agent,
+}
+
+impl RemoteSnake {
+    // Call the snake at `url`, giving up on requests after `timeout`.
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        let agent = Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .build()
+            .into();
+        RemoteSnake {
+            url: url.trim_end_matches('/').to_string(),
+            agent,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // Ask the snake to describe itself.
+    pub fn info(&self) -> Result<SnakeInfo, RemoteError> {
+        let body = self
+            .agent
+            .get(&self.url)
+            .call()?
+            .body_mut()
+            .read_to_string()?;
+        parse(body)
+    }
+
+    // Tell the snake that a game starts.
+    pub fn start(&self, request: &GameRequest) -> Result<(), RemoteError> {
+        self.post("start", request).map(|_| ())
+    }
+
+    // Ask the snake for its move.
+    pub fn next_move(
+        &self,
+        request: &GameRequest,
+    ) -> Result<Move, RemoteError> {
+        let body = self.post("move", request)?;
+        parse::<MoveResponse>(body).map(|response| response.direction)
+    }
+
+    // Tell the snake that the game is over.
+    pub fn end(&self, request: &GameRequest) -> Result<(), RemoteError> {
+        self.post("end", request).map(|_| ())
+    }
+
+    fn post(
+        &self,
+        path: &str,
+        request: &GameRequest,
+    ) -> Result<String, RemoteError> {
+        let json = serde_json::to_string(request).expect("request is JSON");
+        let body = self
+            .agent
+            .post(format!("{}/{path}", self.url))
+            .header("Content-Type", "application/json")
+            .send(json)?
+            .body_mut()
+            .read_to_string()?;
+        Ok(body)
+    }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(
+    body: String,
+) -> Result<T, RemoteError> {
+    serde_json::from_str(&body).map_err(|err| RemoteError::Invalid {
+        body,
+        reason: err.to_string(),
+    })
+}
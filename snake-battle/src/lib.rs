@@ -0,0 +1,6 @@
+//! Battlesnake games: calling snakes over HTTP and serving built-in bots.
+mod client;
+mod standin;
+
+pub use client::{RemoteError, RemoteSnake};
+pub use standin::StandIn;
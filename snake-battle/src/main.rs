@@ -0,0 +1,198 @@
+use apputils::{
+    enable_logging, load_level, option_value, option_values, write_file,
+};
+use log::info;
+use rattlesnake::battlesnake::GameRequest;
+use rattlesnake::{
+    BOTS, Boundary, Field, GameResult, GameState, Outcome, PlayerEvent, Replay,
+    SeededRng, play,
+};
+use snake_battle::{RemoteSnake, StandIn};
+use std::time::Duration;
+use std::{env, process, thread};
+
+const LOG_DIR: &str = "var/log/";
+const LOG_FILE: &str = "snake-battle.log";
+const PORT: u16 = 8000;
+const TIMEOUT_MILLIS: u64 = 500;
+const MAX_TURNS: u64 = 10_000;
+
+fn main() {
+    enable_logging(LOG_DIR, LOG_FILE);
+
+    let args: Vec<String> = env::args().collect();
+    if let Some(name) = option_value(&args, "--serve") {
+        serve(&args, name);
+        return;
+    }
+
+    let urls = option_values(&args, "--snake");
+    if urls.is_empty() {
+        eprintln!(
+            "Name the snakes to play with --snake <url>, or serve a bot \
+             with --serve <name>"
+        );
+        process::exit(1);
+    }
+    let boundary = if args.iter().any(|arg| arg == "--wrap") {
+        Boundary::Wrap
+    } else {
+        Boundary::Walls
+    };
+    let field = match option_value(&args, "--level") {
+        Some(path) => load_level(path).with_boundary(boundary),
+        None => Field::new(11, 11).with_boundary(boundary),
+    };
+    let seed = option_value(&args, "--seed").map_or_else(rand::random, |v| {
+        v.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid seed {v}");
+            process::exit(1);
+        })
+    });
+    let timeout =
+        option_value(&args, "--timeout").map_or(TIMEOUT_MILLIS, |v| {
+            v.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid timeout {v}");
+                process::exit(1);
+            })
+        });
+    let max_turns = option_value(&args, "--max-turns").map_or(MAX_TURNS, |v| {
+        v.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid turn limit {v}");
+            process::exit(1);
+        })
+    });
+
+    let snakes: Vec<RemoteSnake> = urls
+        .iter()
+        .map(|url| RemoteSnake::new(url, Duration::from_millis(timeout)))
+        .collect();
+    for snake in &snakes {
+        match snake.info() {
+            Ok(info) => info!("Snake at {}: {:?}", snake.url(), info),
+            Err(err) => {
+                eprintln!("Failed to reach snake {}: {err}", snake.url());
+                process::exit(1);
+            }
+        }
+    }
+
+    let id = format!("rattlesnake-{seed}");
+    let mut state = GameState::with_players(snakes.len());
+    let mut rng = SeededRng::new(seed);
+    let mut replay = Replay::new(seed, &field, snakes.len());
+    let mut events = vec![PlayerEvent::Idle; snakes.len()];
+    let mut turn = 0;
+    let outcome = loop {
+        replay.record(&events);
+        if let GameResult::GameOver(outcome) =
+            play(&mut state, &field, &events, &mut rng)
+        {
+            break Some(outcome);
+        }
+        if turn == 0 {
+            for (i, snake) in snakes.iter().enumerate() {
+                let request =
+                    GameRequest::new(&id, &state, &field, turn, i, timeout);
+                if let Err(err) = snake.start(&request) {
+                    eprintln!("Snake {} failed to start: {err}", i + 1);
+                }
+            }
+        }
+        if turn == max_turns {
+            break None;
+        }
+        events = next_moves(&snakes, &id, &state, &field, turn, timeout);
+        turn += 1;
+    };
+
+    for (i, snake) in snakes.iter().enumerate() {
+        let request = GameRequest::new(&id, &state, &field, turn, i, timeout);
+        if let Err(err) = snake.end(&request) {
+            eprintln!("Snake {} failed to end: {err}", i + 1);
+        }
+    }
+    if let Some(path) = option_value(&args, "--record")
+        && let Err(err) = write_file(path, &replay.to_json())
+    {
+        eprintln!("Failed to write replay {path}: {err}");
+    }
+
+    match outcome {
+        Some(outcome) => println!("{outcome} after {turn} turns"),
+        None => println!("Stopped after {turn} turns"),
+    }
+    for (i, snake) in state.snakes.iter().enumerate() {
+        let status = match (&snake.death, outcome) {
+            (Some(death), _) => format!("{death}"),
+            (None, Some(Outcome::Winner(winner))) if winner == i => {
+                "won".to_string()
+            }
+            _ => "alive".to_string(),
+        };
+        println!(
+            "Snake {} ({}): length {}, {status}",
+            i + 1,
+            snakes[i].url(),
+            snake.body.len()
+        );
+    }
+}
+
+// Ask every living snake for its move at once. Snakes that fail to answer
+// keep going in their direction, as in Battlesnake.
+fn next_moves(
+    snakes: &[RemoteSnake],
+    id: &str,
+    state: &GameState,
+    field: &Field,
+    turn: u64,
+    timeout: u64,
+) -> Vec<PlayerEvent> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = snakes
+            .iter()
+            .enumerate()
+            .map(|(i, snake)| {
+                scope.spawn(move || {
+                    if !state.snakes[i].alive {
+                        return PlayerEvent::Idle;
+                    }
+                    let request =
+                        GameRequest::new(id, state, field, turn, i, timeout);
+                    match snake.next_move(&request) {
+                        Ok(direction) => direction.into(),
+                        Err(err) => {
+                            eprintln!("Snake {} on turn {turn}: {err}", i + 1);
+                            PlayerEvent::Idle
+                        }
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("request panicked"))
+            .collect()
+    })
+}
+
+// Serve the built-in bot `name` until killed.
+fn serve(args: &[String], name: &str) {
+    let Some(stand_in) = StandIn::new(name) else {
+        eprintln!("Unknown bot {name}, choose one of {}", BOTS.join(", "));
+        process::exit(1);
+    };
+    let port = option_value(args, "--port").map_or(PORT, |v| {
+        v.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid port {v}");
+            process::exit(1);
+        })
+    });
+    let addr = format!("127.0.0.1:{port}");
+    eprintln!("Serving {name} at http://{addr}");
+    if let Err(err) = stand_in.serve(&addr) {
+        eprintln!("Failed to serve at {addr}: {err}");
+        process::exit(1);
+    }
+}
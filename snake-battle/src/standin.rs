@@ -0,0 +1,136 @@
+//! A built-in bot served over the Battlesnake API, to play against without
+//! any network beyond localhost.
+use log::{debug, warn};
+use rattlesnake::battlesnake::{
+    API_VERSION, GameRequest, Move, MoveResponse, SnakeInfo,
+};
+use rattlesnake::{Controller, bot, find_direction};
+use std::collections::HashMap;
+use tiny_http::{Header, Method, Response, Server};
+
+pub struct StandIn {
+    name: String,
+    // The bot of every game in progress, by game and snake id.
+    games: HashMap<(String, String), Box<dyn Controller>>,
+}
+
+impl StandIn {
+    // Serve the built-in bot `name`, or `None` if there is no such bot.
+    pub fn new(name: &str) -> Option<Self> {
+        bot(name)?;
+        Some(StandIn {
+            name: name.to_string(),
+            games: HashMap::new(),
+        })
+    }
+
+    // Answer requests at `addr`, e.g. `127.0.0.1:8000`, one at a time and
+    // forever.
+    pub fn serve(
+        mut self,
+        addr: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let server = Server::http(addr)?;
+        let json = Header::from_bytes("Content-Type", "application/json")
+            .expect("header is valid");
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            let (status, answer) = match request
+                .as_reader()
+                .read_to_string(&mut body)
+            {
+                Ok(_) => self.answer(request.method(), request.url(), &body),
+                Err(err) => (400, err.to_string()),
+            };
+            let response = Response::from_string(answer)
+                .with_status_code(status)
+                .with_header(json.clone());
+            if let Err(err) = request.respond(response) {
+                warn!("Failed to answer request: {err}");
+            }
+        }
+        Ok(())
+    }
+
+    // Status and body of the answer to a request.
+    fn answer(
+        &mut self,
+        method: &Method,
+        path: &str,
+        body: &str,
+    ) -> (u16, String) {
+        debug!("{method} {path}");
+        let path = path.trim_end_matches('/');
+        match (method, path) {
+            (Method::Get, "") => {
+                let info = SnakeInfo {
+                    apiversion: API_VERSION.to_string(),
+                    author: "rattlesnake".to_string(),
+                    color: "#2e8b57".to_string(),
+                    head: "default".to_string(),
+                    tail: "default".to_string(),
+                    version: self.name.clone(),
+                };
+                (200, to_json(&info))
+            }
+            (Method::Post, "/start" | "/move" | "/end") => {
+                let request: GameRequest = match serde_json::from_str(body) {
+                    Ok(request) => request,
+                    Err(err) => return (400, err.to_string()),
+                };
+                let key = (request.game.id.clone(), request.you.id.clone());
+                match path {
+                    "/start" => {
+                        let controller =
+                            bot(&self.name).expect("bot was checked");
+                        self.games.insert(key, controller);
+                        (200, "{}".to_string())
+                    }
+                    "/end" => {
+                        self.games.remove(&key);
+                        (200, "{}".to_string())
+                    }
+                    _ => match self.next_move(key, &request) {
+                        Some(direction) => {
+                            let response = MoveResponse {
+                                direction,
+                                shout: None,
+                            };
+                            (200, to_json(&response))
+                        }
+                        None => (400, "you are not on the board".to_string()),
+                    },
+                }
+            }
+            _ => (404, "not found".to_string()),
+        }
+    }
+
+    // Ask the game's bot for a move, keeping straight on when it has none.
+    fn next_move(
+        &mut self,
+        key: (String, String),
+        request: &GameRequest,
+    ) -> Option<Move> {
+        let (field, state, you) = request.to_game()?;
+        // Games joined without `/start` get a bot as well
+        let name = &self.name;
+        let controller = self
+            .games
+            .entry(key)
+            .or_insert_with(|| bot(name).expect("bot was checked"));
+        let event = controller.next_event(&state, &field, you);
+        // Resolves turns as well as idling
+        let direction = find_direction(&event, state.snakes[you].direction);
+        Some(match direction {
+            (0, 1) => Move::Down,
+            (-1, 0) => Move::Left,
+            (1, 0) => Move::Right,
+            _ => Move::Up,
+        })
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("answer is JSON")
+}
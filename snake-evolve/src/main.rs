@@ -0,0 +1,208 @@
+use apputils::{
+    enable_logging, load_genome, load_level, option_value, parse_number,
+    write_file,
+};
+use rattlesnake::{
+    Boundary, Controller, Field, GameResult, GameState, Genome, NeuralBot,
+    PlayerEvent, Random, SeededRng, play,
+};
+use std::{env, process, thread};
+
+const LOG_DIR: &str = "var/log/";
+const LOG_FILE: &str = "snake-evolve.log";
+const GENOME_FILE: &str = "var/genomes/best.json";
+const POPULATION: usize = 100;
+const GENERATIONS: usize = 100;
+const HIDDEN: usize = 16;
+const GAMES: usize = 5;
+const MAX_TICKS: u64 = 5000;
+// Share of each generation carried over unchanged.
+const ELITE: f64 = 0.1;
+// Genomes drawn per parent selection, of which the fittest wins.
+const TOURNAMENT: usize = 3;
+const MUTATION_RATE: f32 = 0.1;
+const MUTATION_STRENGTH: f32 = 0.5;
+
+struct Options {
+    field: Field,
+    games: usize,
+    max_ticks: u64,
+}
+
+fn main() {
+    enable_logging(LOG_DIR, LOG_FILE);
+
+    let args: Vec<String> = env::args().collect();
+    let boundary = if args.iter().any(|arg| arg == "--wrap") {
+        Boundary::Wrap
+    } else {
+        Boundary::Walls
+    };
+    let field = match option_value(&args, "--level") {
+        Some(path) => load_level(path).with_boundary(boundary),
+        None => Field::new(20, 20).with_boundary(boundary),
+    };
+    let options = Options {
+        field,
+        games: parse_number(&args, "--games", GAMES),
+        max_ticks: parse_number(&args, "--max-ticks", MAX_TICKS),
+    };
+    let size = parse_number(&args, "--population", POPULATION).max(2);
+    let generations = parse_number(&args, "--generations", GENERATIONS);
+    let output = option_value(&args, "--output").unwrap_or(GENOME_FILE);
+    let mut rng = SeededRng::new(parse_number(&args, "--seed", 0));
+
+    // Start from random networks, or from mutations of a checkpoint
+    let mut population: Vec<Genome> = match option_value(&args, "--resume") {
+        Some(path) => {
+            let genome = load_genome(path);
+            (0..size)
+                .map(|i| {
+                    let mut child = genome.clone();
+                    if i > 0 {
+                        child.mutate(
+                            MUTATION_RATE,
+                            MUTATION_STRENGTH,
+                            &mut rng,
+                        );
+                    }
+                    child
+                })
+                .collect()
+        }
+        None => {
+            let hidden =
+                option_value(&args, "--hidden").map_or(vec![HIDDEN], |value| {
+                    value
+                        .split(',')
+                        .map(|size| size.parse().ok())
+                        .collect::<Option<Vec<usize>>>()
+                        .unwrap_or_else(|| {
+                            eprintln!(
+                                "Invalid hidden layers {value}, expected \
+                                 e.g. 16 or 16,8"
+                            );
+                            process::exit(1);
+                        })
+                });
+            (0..size)
+                .map(|_| Genome::random(&hidden, &mut rng))
+                .collect()
+        }
+    };
+
+    let mut best_fitness = f64::NEG_INFINITY;
+    for generation in 1..=generations {
+        // Every genome of a generation plays the same games
+        let seeds: Vec<u64> =
+            (0..options.games).map(|_| rng.next_u64()).collect();
+        let fitness = evaluate_all(&options, &population, &seeds);
+
+        let mut order: Vec<usize> = (0..population.len()).collect();
+        order.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]));
+        let best = order[0];
+        let mean = fitness.iter().sum::<f64>() / fitness.len() as f64;
+        println!(
+            "Generation {generation}: best {:.2}, mean {mean:.2}",
+            fitness[best]
+        );
+        if fitness[best] > best_fitness {
+            best_fitness = fitness[best];
+            if let Err(err) = write_file(output, &population[best].to_json()) {
+                eprintln!("Failed to write genome {output}: {err}");
+                process::exit(1);
+            }
+        }
+
+        population = breed(&population, &fitness, &order, &mut rng);
+    }
+    println!("Best fitness {best_fitness:.2}, saved to {output}");
+}
+
+// The next generation: the elite unchanged, and children of parents picked
+// by tournament selection.
+fn breed(
+    population: &[Genome],
+    fitness: &[f64],
+    order: &[usize],
+    rng: &mut SeededRng,
+) -> Vec<Genome> {
+    let elite = ((population.len() as f64 * ELITE) as usize).max(1);
+    let mut next: Vec<Genome> = order[..elite]
+        .iter()
+        .map(|&i| population[i].clone())
+        .collect();
+    let select = |rng: &mut SeededRng| {
+        (0..TOURNAMENT)
+            .map(|_| rng.index(population.len()))
+            .max_by(|a, b| fitness[*a].total_cmp(&fitness[*b]))
+            .expect("tournament is not empty")
+    };
+    while next.len() < population.len() {
+        let (a, b) = (select(rng), select(rng));
+        let mut child = population[a].crossover(&population[b], rng);
+        child.mutate(MUTATION_RATE, MUTATION_STRENGTH, rng);
+        next.push(child);
+    }
+    next
+}
+
+// Fitness of every genome, spreading the games over all cores.
+fn evaluate_all(
+    options: &Options,
+    population: &[Genome],
+    seeds: &[u64],
+) -> Vec<f64> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = population.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = population
+            .chunks(chunk)
+            .map(|genomes| {
+                scope.spawn(move || {
+                    genomes
+                        .iter()
+                        .map(|genome| evaluate(options, genome, seeds))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("evaluation panicked"))
+            .collect()
+    })
+}
+
+// Mean of food eaten over the games, with a little extra for surviving
+// longer. Snakes that go without food for as many ticks as the field has
+// cells are stopped, so that circling forever does not pay.
+fn evaluate(options: &Options, genome: &Genome, seeds: &[u64]) -> f64 {
+    let field = &options.field;
+    let mut total = 0.0;
+    for &seed in seeds {
+        let mut bot = NeuralBot::new(genome.clone());
+        let mut state = GameState::new();
+        let mut rng = SeededRng::new(seed);
+        let mut event = PlayerEvent::Idle;
+        let mut ticks = 0;
+        let mut hungry = 0;
+        while ticks < options.max_ticks && hungry < field.floor() {
+            ticks += 1;
+            let score = state.snakes[0].score;
+            if let GameResult::GameOver(_) =
+                play(&mut state, field, &[event], &mut rng)
+            {
+                break;
+            }
+            hungry = if state.snakes[0].score > score {
+                0
+            } else {
+                hungry + 1
+            };
+            event = bot.next_event(&state, field, 0);
+        }
+        total += state.snakes[0].score as f64 + ticks as f64 * 0.001;
+    }
+    total / seeds.len().max(1) as f64
+}
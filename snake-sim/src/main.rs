@@ -0,0 +1,264 @@
+use apputils::{enable_logging, load_level, option_value, write_file};
+use rattlesnake::{
+    BOTS, Boundary, Controller, Death, ExternalBot, Field, GameResult,
+    GameState, Outcome, PlayerEvent, SeededRng, bot, play,
+};
+use serde::Serialize;
+use std::ops::Range;
+use std::time::Duration;
+use std::{env, process, thread};
+
+const LOG_DIR: &str = "var/log/";
+const LOG_FILE: &str = "snake-sim.log";
+const SEEDS: Range<u64> = 0..1000;
+const MAX_TICKS: u64 = 1_000_000;
+const BOT_TIMEOUT_MILLIS: u64 = 100;
+
+// Result of one simulated game.
+#[derive(Debug, Serialize)]
+struct Record {
+    seed: u64,
+    score: u8,
+    length: usize,
+    ticks: u64,
+    // `win`, `wall`, `self-bite`, `snake`, `head-on` or `timeout`.
+    end: &'static str,
+    // How the game ended in words.
+    reason: String,
+    // Number of errors of an external bot.
+    errors: usize,
+}
+
+// Who plays the games.
+enum Player {
+    Bot(String),
+    // Command of an external bot and its time per move.
+    External(String, Duration),
+}
+
+struct Options {
+    player: Player,
+    field: Field,
+    seeds: Range<u64>,
+    max_ticks: u64,
+}
+
+fn main() {
+    enable_logging(LOG_DIR, LOG_FILE);
+
+    let args: Vec<String> = env::args().collect();
+    let player = match (
+        option_value(&args, "--bot"),
+        option_value(&args, "--bot-cmd"),
+    ) {
+        (Some(name), None) if bot(name).is_some() => {
+            Player::Bot(name.to_string())
+        }
+        (Some(name), None) => {
+            eprintln!("Unknown bot {name}, choose one of {}", BOTS.join(", "));
+            process::exit(1);
+        }
+        (None, Some(command)) => {
+            let timeout = option_value(&args, "--bot-timeout").map_or(
+                BOT_TIMEOUT_MILLIS,
+                |value| {
+                    value.parse().unwrap_or_else(|_| {
+                        eprintln!("Invalid bot timeout {value}");
+                        process::exit(1);
+                    })
+                },
+            );
+            Player::External(
+                command.to_string(),
+                Duration::from_millis(timeout),
+            )
+        }
+        _ => {
+            eprintln!(
+                "Choose either a bot with --bot ({}) or an external bot with \
+                 --bot-cmd",
+                BOTS.join(", ")
+            );
+            process::exit(1);
+        }
+    };
+    let boundary = if args.iter().any(|arg| arg == "--wrap") {
+        Boundary::Wrap
+    } else {
+        Boundary::Walls
+    };
+    let field = match option_value(&args, "--level") {
+        Some(path) => load_level(path).with_boundary(boundary),
+        None => Field::new(40, 20).with_boundary(boundary),
+    };
+    let seeds = option_value(&args, "--seeds").map_or(SEEDS, |value| {
+        parse_range(value).unwrap_or_else(|| {
+            eprintln!("Invalid seed range {value}, expected e.g. 0..1000");
+            process::exit(1);
+        })
+    });
+    let max_ticks = option_value(&args, "--max-ticks").map_or(MAX_TICKS, |v| {
+        v.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid tick limit {v}");
+            process::exit(1);
+        })
+    });
+    let format = option_value(&args, "--format").unwrap_or("csv");
+    if format != "csv" && format != "json" {
+        eprintln!("Unknown format {format}, choose csv or json");
+        process::exit(1);
+    }
+
+    let options = Options {
+        player,
+        field,
+        seeds,
+        max_ticks,
+    };
+    let records = simulate_all(&options);
+
+    let output = match format {
+        "json" => to_json(&records),
+        _ => to_csv(&records),
+    };
+    match option_value(&args, "--output") {
+        Some(path) => {
+            if let Err(err) = write_file(path, &output) {
+                eprintln!("Failed to write results {path}: {err}");
+                process::exit(1);
+            }
+        }
+        None => print!("{output}"),
+    }
+    summarize(&records);
+}
+
+// Simulate every seed, spreading the games over all cores.
+fn simulate_all(options: &Options) -> Vec<Record> {
+    let games = options.seeds.end.saturating_sub(options.seeds.start);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = games.div_ceil(threads as u64).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads as u64)
+            .map(|i| {
+                let start = options.seeds.start + i * chunk;
+                let end = (start + chunk).min(options.seeds.end);
+                scope.spawn(move || {
+                    (start..end)
+                        .map(|seed| simulate(options, seed))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("simulation panicked"))
+            .collect()
+    })
+}
+
+// Play a single-player game with the bot until it ends or times out.
+fn simulate(options: &Options, seed: u64) -> Record {
+    let mut controller: Box<dyn Controller> = match &options.player {
+        Player::Bot(name) => bot(name).expect("bot was checked"),
+        Player::External(command, timeout) => {
+            match ExternalBot::spawn(command, *timeout) {
+                Ok(bot) => Box::new(bot),
+                Err(err) => {
+                    eprintln!("Failed to start bot {command}: {err}");
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    let mut errors = 0;
+    let field = &options.field;
+    let mut state = GameState::new();
+    let mut rng = SeededRng::new(seed);
+    let mut event = PlayerEvent::Idle;
+    let mut ticks = 0;
+    let outcome = loop {
+        if ticks == options.max_ticks {
+            break None;
+        }
+        ticks += 1;
+        match play(&mut state, field, &[event], &mut rng) {
+            GameResult::Continue => {}
+            GameResult::GameOver(outcome) => break Some(outcome),
+        }
+        event = controller.next_event(&state, field, 0);
+        errors += controller.take_errors().len();
+    };
+
+    let snake = &state.snakes[0];
+    let (end, reason) = match outcome {
+        Some(outcome) => (end_name(&outcome), outcome.to_string()),
+        None => ("timeout", format!("Stopped after {ticks} ticks")),
+    };
+    Record {
+        seed,
+        score: snake.score,
+        length: snake.body.len(),
+        ticks,
+        end,
+        reason,
+        errors,
+    }
+}
+
+fn end_name(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Outcome::Win | Outcome::Winner(_) => "win",
+        Outcome::Died(Death::Wall(_)) => "wall",
+        Outcome::Died(Death::SelfBite(_)) => "self-bite",
+        Outcome::Died(Death::Snake { .. }) => "snake",
+        Outcome::Died(Death::HeadOn { .. }) => "head-on",
+        Outcome::Quit => "quit",
+        Outcome::Draw => "draw",
+    }
+}
+
+fn to_csv(records: &[Record]) -> String {
+    let mut csv = String::from("seed,score,length,ticks,end,reason,errors\n");
+    for r in records {
+        csv.push_str(&format!(
+            "{},{},{},{},{},\"{}\",{}\n",
+            r.seed,
+            r.score,
+            r.length,
+            r.ticks,
+            r.end,
+            r.reason.replace('"', "\"\""),
+            r.errors
+        ));
+    }
+    csv
+}
+
+fn to_json(records: &[Record]) -> String {
+    let mut json =
+        serde_json::to_string_pretty(records).expect("records serialize");
+    json.push('\n');
+    json
+}
+
+// Print totals to stderr, out of the way of the results on stdout.
+fn summarize(records: &[Record]) {
+    let games = records.len().max(1) as f64;
+    let wins = records.iter().filter(|r| r.end == "win").count();
+    let score: f64 = records.iter().map(|r| r.score as f64).sum();
+    let ticks: f64 = records.iter().map(|r| r.ticks as f64).sum();
+    eprintln!(
+        "{} games, {} wins, mean score {:.2}, mean ticks {:.1}",
+        records.len(),
+        wins,
+        score / games,
+        ticks / games
+    );
+}
+
+// Parse a range of the form `start..end`.
+fn parse_range(value: &str) -> Option<Range<u64>> {
+    let (start, end) = value.split_once("..")?;
+    Some(start.parse().ok()?..end.parse().ok()?)
+}
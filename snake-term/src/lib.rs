@@ -1,26 +1,54 @@
+use crossterm::style::Stylize;
 use crossterm::{ExecutableCommand, cursor, event, style, terminal};
-use rattlesnake::PlayerEvent;
+use rattlesnake::{
+    Boundary, Field, FoodKind, GameEvent, GameState, Outcome, Phase,
+    PlayerEvent,
+};
 use std::fmt;
 use std::io::{Stdout, Write, stdout};
 use std::time;
 
-const SYMBOL_FOOD: &str = "@";
-const SYMBOL_SNAKE: &str = "S";
+// Food by kind, in the order of `FoodKind::ALL`.
+const SYMBOL_FOOD: [&str; 8] = ["@", "$", "%", "+", "-", "?", "&", "~"];
+const SYMBOL_SNAKES: [&str; 4] = ["S", "Z", "N", "M"];
 const SYMBOL_WALL: &str = "#";
+const SYMBOL_WRAP: &str = ":";
 const SYMBOL_EMPTY: &str = " ";
 
+// Controls for watching a replay.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Playback {
+    TogglePause,
+    Step,
+    ToggleFastForward,
+    Quit,
+    Continue,
+}
+
 pub struct TerminalUI {
     stdout: Stdout,
-    tail_cache: (u16, u16),
-    food_cache: Vec<(u16, u16)>,
+    status_row: u16,
+    // Whether the whole state has been drawn once.
+    drawn: bool,
+    field_width: u16,
+    field_height: u16,
+    walls: Vec<(u16, u16)>,
+    border: &'static str,
+    // Cells covered by the pause overlay: first column, row and width.
+    overlay: Option<(u16, u16, u16)>,
 }
 
 impl TerminalUI {
     pub fn new() -> Self {
         TerminalUI {
             stdout: stdout(),
-            tail_cache: (0, 0),
-            food_cache: Vec::new(),
+            status_row: 0,
+            drawn: false,
+            field_width: 0,
+            field_height: 0,
+            walls: Vec::new(),
+            border: SYMBOL_WALL,
+            overlay: None,
         }
     }
 }
@@ -32,10 +60,14 @@ impl Default for TerminalUI {
 }
 
 impl TerminalUI {
-    pub fn init(&mut self, field_width: u16, field_height: u16) {
+    pub fn init(&mut self, field: &Field) {
         self.prepare_terminal();
-        self.draw_field(field_width, field_height);
-        self.clear_field(field_width, field_height);
+        self.clear_field(field.width(), field.height());
+        self.draw_field(field);
+        self.status_row = field.height() + 2;
+        self.field_width = field.width();
+        self.field_height = field.height();
+        self.walls = field.walls().copied().collect();
         self.flush();
     }
 
@@ -43,54 +75,273 @@ impl TerminalUI {
         self.reset_terminal();
     }
 
-    pub fn render(&mut self, snake: &[(u16, u16)], food: &Vec<(u16, u16)>) {
-        for pos in food {
-            if self.food_cache.contains(pos) {
-                continue;
+    // Draw what changed during the last tick.
+    pub fn render(&mut self, state: &GameState) {
+        // Draw the whole state on the first render, e.g. of a resumed game
+        if !self.drawn {
+            self.draw_state(state);
+            self.draw_effects(state);
+            self.draw_phase(state);
+            self.drawn = true;
+            self.flush();
+            return;
+        }
+
+        // Clear first so that nothing drawn this tick gets erased
+        let mut died = false;
+        for event in state.events() {
+            match *event {
+                // A ghost's tail may leave a cell its body still crosses
+                GameEvent::Moved { vacated, .. }
+                    if state.alive().any(|s| s.body.contains(&vacated)) => {}
+                GameEvent::Moved { vacated, .. }
+                | GameEvent::Shrank { vacated, .. } => self.clear(vacated),
+                GameEvent::FoodExpired { at }
+                | GameEvent::FoodMoved { from: at, .. } => self.clear(at),
+                GameEvent::Died { snake, .. } => {
+                    // The head moved into whatever killed the snake and was
+                    // never drawn.
+                    for pos in state.snakes[snake].body.iter().skip(1) {
+                        self.clear(*pos);
+                    }
+                    died = true;
+                }
+                _ => {}
+            }
+        }
+
+        for event in state.events() {
+            match *event {
+                GameEvent::Moved { snake, head, .. }
+                    if state.snakes[snake].alive =>
+                {
+                    self.draw_snake(snake, &head)
+                }
+                GameEvent::Grew { snake, at } => self.draw_snake(snake, &at),
+                GameEvent::FoodSpawned { at, kind } => {
+                    self.draw_food(&at, kind)
+                }
+                GameEvent::FoodMoved { to, .. } => {
+                    // Food pulled onto a head was eaten
+                    let eaten = state.alive().any(|s| s.head() == Some(to));
+                    let food = state.food.iter().find(|food| food.pos == to);
+                    if let Some(food) = food.filter(|_| !eaten) {
+                        self.draw_food(&to, food.kind);
+                    }
+                }
+                _ => {}
             }
-            self.draw_food(pos);
+        }
+        if died {
+            // Cleared bodies may have covered food or other snakes' heads
+            self.draw_state(state);
+        }
+        let phase_changed = state.events().iter().any(|event| {
+            matches!(
+                event,
+                GameEvent::Paused
+                    | GameEvent::Countdown { .. }
+                    | GameEvent::Resumed
+            )
+        });
+        if phase_changed {
+            self.draw_phase(state);
+        }
+        let ended = state
+            .events()
+            .iter()
+            .any(|event| matches!(event, GameEvent::PowerEnded { .. }));
+        if ended || !state.effects.is_empty() {
+            self.draw_effects(state);
         }
 
-        self.draw_snake(&snake[0]);
+        self.flush();
+    }
 
-        let tail = snake[snake.len() - 1];
-        if tail != self.tail_cache {
-            self.clear(self.tail_cache);
+    // Show over the field whether the game is paused or counting down.
+    fn draw_phase(&mut self, state: &GameState) {
+        match state.phase {
+            Phase::Running => self.clear_overlay(state),
+            Phase::Paused => {
+                self.draw_overlay(state, " Paused, press p to resume ")
+            }
+            Phase::Countdown(remaining) => {
+                self.draw_overlay(state, &format!(" {remaining} "))
+            }
         }
+    }
 
-        self.tail_cache = tail;
-        self.food_cache = food.clone();
+    // Draw `text` across the middle row of the field, cut to its width.
+    fn draw_overlay(&mut self, state: &GameState, text: &str) {
+        self.clear_overlay(state);
+        let text: String =
+            text.chars().take(self.field_width as usize).collect();
+        let len = text.chars().count() as u16;
+        let x = 1 + (self.field_width - len) / 2;
+        let y = 1 + self.field_height / 2;
+        self.draw(style::style(text).reverse(), x, y);
+        self.overlay = Some((x, y, len));
+    }
 
-        self.flush();
+    // Remove the overlay and redraw what it covered.
+    fn clear_overlay(&mut self, state: &GameState) {
+        let Some((x, y, len)) = self.overlay.take() else {
+            return;
+        };
+        for i in 0..len {
+            self.clear((x + i, y));
+        }
+        for pos in self.walls.clone() {
+            if pos.1 == y && (x..x + len).contains(&pos.0) {
+                self.draw(SYMBOL_WALL, pos.0, pos.1);
+            }
+        }
+        self.draw_state(state);
     }
 
-    // Poll event for `millis` milliseconds.
-    pub fn poll(&self, millis: u64) -> PlayerEvent {
-        if event::poll(time::Duration::from_millis(millis)).unwrap()
-            && let event::Event::Key(event) = event::read().unwrap()
-        {
+    // Show the power-ups in effect and their ticks left over the top border,
+    // each after the symbol of the snake that has it.
+    fn draw_effects(&mut self, state: &GameState) {
+        let border = self.border;
+        for x in 0..self.field_width + 2 {
+            self.draw(border, x, 0);
+        }
+        let text: String = state
+            .effects
+            .iter()
+            .map(|effect| {
+                let snake = SYMBOL_SNAKES[effect.snake % SYMBOL_SNAKES.len()];
+                format!(" {snake} {} {} ", effect.power.name(), effect.ticks)
+            })
+            .collect();
+        if text.is_empty() {
+            return;
+        }
+        let text: String =
+            text.chars().take(self.field_width as usize).collect();
+        self.draw(style::style(text).reverse(), 1, 0);
+    }
+
+    // Read the keys pressed during the next `millis` milliseconds, in order.
+    // Quitting or saving ends the wait early.
+    pub fn poll(&self, millis: u64) -> Vec<PlayerEvent> {
+        let deadline =
+            time::Instant::now() + time::Duration::from_millis(millis);
+        let mut events = Vec::new();
+        loop {
+            let remaining =
+                deadline.saturating_duration_since(time::Instant::now());
+            if remaining.is_zero() || !event::poll(remaining).unwrap() {
+                return events;
+            }
+            // Some terminals report releases too, which are not presses
+            let event::Event::Key(event) = event::read().unwrap() else {
+                continue;
+            };
+            if event.kind != event::KeyEventKind::Press {
+                continue;
+            }
             let key_event = match event.code {
                 event::KeyCode::Up => PlayerEvent::Up,
                 event::KeyCode::Down => PlayerEvent::Down,
                 event::KeyCode::Left => PlayerEvent::Left,
                 event::KeyCode::Right => PlayerEvent::Right,
+                event::KeyCode::Char('z') => PlayerEvent::TurnLeft,
+                event::KeyCode::Char('x') => PlayerEvent::TurnRight,
+                event::KeyCode::Char('p') => PlayerEvent::Pause,
                 event::KeyCode::Char('q') => PlayerEvent::Quit,
-                _ => PlayerEvent::Idle,
+                event::KeyCode::Char('s') => PlayerEvent::SaveAndQuit,
+                _ => continue,
             };
-            return key_event;
+            events.push(key_event);
+            if matches!(key_event, PlayerEvent::Quit | PlayerEvent::SaveAndQuit)
+            {
+                return events;
+            }
+        }
+    }
+
+    // Show how the game ended and wait for a key; `q` quits.
+    pub fn game_over(&mut self, outcome: &Outcome) -> PlayerEvent {
+        self.status(&format!(
+            "Game over! {outcome}.\r\nPress any key to play again, q to quit."
+        ));
+        loop {
+            if let event::Event::Key(event) = event::read().unwrap()
+                && event.kind == event::KeyEventKind::Press
+            {
+                return match event.code {
+                    event::KeyCode::Char('q') => PlayerEvent::Quit,
+                    _ => PlayerEvent::Idle,
+                };
+            }
+        }
+    }
+
+    // Poll replay controls for `millis` milliseconds.
+    pub fn poll_playback(&self, millis: u64) -> Playback {
+        if event::poll(time::Duration::from_millis(millis)).unwrap()
+            && let event::Event::Key(event) = event::read().unwrap()
+            && event.kind == event::KeyEventKind::Press
+        {
+            return match event.code {
+                event::KeyCode::Char(' ') => Playback::TogglePause,
+                event::KeyCode::Right | event::KeyCode::Char('n') => {
+                    Playback::Step
+                }
+                event::KeyCode::Char('f') => Playback::ToggleFastForward,
+                event::KeyCode::Char('q') => Playback::Quit,
+                _ => Playback::Continue,
+            };
+        }
+        Playback::Continue
+    }
+
+    // Show `text` in the line below the field.
+    pub fn status(&mut self, text: &str) {
+        self.stdout
+            .execute(cursor::MoveTo(0, self.status_row))
+            .unwrap()
+            .execute(terminal::Clear(terminal::ClearType::CurrentLine))
+            .unwrap()
+            .execute(style::Print(text))
+            .unwrap();
+        self.flush();
+    }
+
+    fn draw_field(&mut self, field: &Field) {
+        let border = match field.boundary() {
+            Boundary::Walls => SYMBOL_WALL,
+            Boundary::Wrap => SYMBOL_WRAP,
+        };
+        self.draw_box(&border, 0, 0, field.width() + 2, field.height() + 2);
+        self.border = border;
+        for pos in field.walls() {
+            self.draw(SYMBOL_WALL, pos.0, pos.1);
         }
-        PlayerEvent::Idle
     }
-    fn draw_field(&mut self, width: u16, height: u16) {
-        self.draw_box(&SYMBOL_WALL, 0, 0, width + 2, height + 2);
+
+    // Draw all food and living snakes.
+    fn draw_state(&mut self, state: &GameState) {
+        for food in &state.food {
+            self.draw_food(&food.pos, food.kind);
+        }
+        for (i, snake) in state.snakes.iter().enumerate() {
+            if snake.alive {
+                for pos in &snake.body {
+                    self.draw_snake(i, pos);
+                }
+            }
+        }
     }
 
-    fn draw_snake(&mut self, pos: &(u16, u16)) {
-        self.draw(SYMBOL_SNAKE, pos.0, pos.1);
+    fn draw_snake(&mut self, idx: usize, pos: &(u16, u16)) {
+        let symbol = SYMBOL_SNAKES[idx % SYMBOL_SNAKES.len()];
+        self.draw(symbol, pos.0, pos.1);
     }
 
-    fn draw_food(&mut self, pos: &(u16, u16)) {
-        self.draw(SYMBOL_FOOD, pos.0, pos.1);
+    fn draw_food(&mut self, pos: &(u16, u16), kind: FoodKind) {
+        self.draw(SYMBOL_FOOD[kind as usize], pos.0, pos.1);
     }
 
     fn clear(&mut self, pos: (u16, u16)) {
@@ -164,7 +415,3 @@ impl TerminalUI {
         terminal::disable_raw_mode().unwrap();
     }
 }
-
-pub fn random_range(low: u16, high: u16) -> u16 {
-    rand::random_range(low..high)
-}
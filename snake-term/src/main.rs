@@ -1,44 +1,299 @@
-use apputils::enable_logging;
-use rattlesnake::{Field, GameState, PlayerEvent, RandomRange, play};
-use snake_term::{TerminalUI, random_range};
+use apputils::{
+    enable_logging, load_genome, load_level, option_value, write_file,
+};
+use rattlesnake::{
+    BOTS, Boundary, Controller, ExternalBot, Field, FoodRules, GameResult,
+    GameState, InputQueue, NeuralBot, Outcome, Phase, PlayerEvent, Replay,
+    ReplayPlayer, SavedGame, SeededRng, Speed, TICK_MILLIS, bot, play,
+};
+use snake_term::{Playback, TerminalUI};
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
 
 const LOG_DIR: &str = "var/log/";
 const LOG_FILE: &str = "snake-term.log";
+const SAVE_FILE: &str = "var/save/snake-term.json";
+const FAST_FORWARD: u64 = 4;
+const BOT_TIMEOUT_MILLIS: u64 = 100;
 
 fn main() {
     enable_logging(LOG_DIR, LOG_FILE);
 
-    let width: u16 = 40;
-    let height: u16 = 20;
-    let field = Field::new(width, height);
+    let args: Vec<String> = env::args().collect();
+    if let Some(path) = option_value(&args, "--replay") {
+        watch_replay(path);
+        return;
+    }
+
+    let save_path = option_value(&args, "--save").unwrap_or(SAVE_FILE);
+    let mut resumed = if args.iter().any(|arg| arg == "--resume") {
+        Some(load_save(save_path))
+    } else {
+        None
+    };
+
+    let boundary = if args.iter().any(|arg| arg == "--wrap") {
+        Boundary::Wrap
+    } else {
+        Boundary::Walls
+    };
+    let speed: Speed =
+        option_value(&args, "--speed").map_or_else(Speed::default, |value| {
+            value.parse().unwrap_or_else(|err| {
+                eprintln!("Invalid speed {value}: {err}");
+                process::exit(1);
+            })
+        });
+    let food_rules: FoodRules = option_value(&args, "--food").map_or_else(
+        FoodRules::default,
+        |value| {
+            value.parse().unwrap_or_else(|err| {
+                eprintln!("Invalid food {value}: {err}");
+                process::exit(1);
+            })
+        },
+    );
+    // A resumed game keeps its speed and food
+    let field = match (&resumed, option_value(&args, "--level")) {
+        (Some(save), _) => save.field.clone(),
+        (None, Some(path)) => load_level(path)
+            .with_boundary(boundary)
+            .with_speed(speed)
+            .with_food_rules(food_rules),
+        (None, None) => Field::new(40, 20)
+            .with_boundary(boundary)
+            .with_speed(speed)
+            .with_food_rules(food_rules),
+    };
 
     let mut ui: TerminalUI;
     let mut state: GameState;
     let mut event: PlayerEvent;
-    let random_range: RandomRange = random_range;
+    let mut rng: SeededRng;
+    let mut replay: Option<Replay>;
+    let seed: Option<u64> = option_value(&args, "--seed").map(|value| {
+        value.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid seed {value}");
+            process::exit(1);
+        })
+    });
+    let record = option_value(&args, "--record");
+    let bot_name = option_value(&args, "--bot");
+    if let Some(name) = bot_name
+        && bot(name).is_none()
+    {
+        eprintln!("Unknown bot {name}, choose one of {}", BOTS.join(", "));
+        process::exit(1);
+    }
+    let bot_command = option_value(&args, "--bot-cmd");
+    let bot_timeout = option_value(&args, "--bot-timeout").map_or(
+        BOT_TIMEOUT_MILLIS,
+        |value| {
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid bot timeout {value}");
+                process::exit(1);
+            })
+        },
+    );
+    let genome = option_value(&args, "--genome").map(load_genome);
+    let mut errors = Vec::new();
     loop {
-        state = GameState::new();
+        if let Some(save) = resumed.take() {
+            // A resumed game lacks its beginning and cannot be recorded
+            state = save.state;
+            rng = save.rng;
+            replay = None;
+        } else {
+            // Every game gets its own seed so that it can be replayed on its
+            // own
+            let game_seed = seed.unwrap_or_else(rand::random);
+            state = GameState::new();
+            rng = SeededRng::new(game_seed);
+            replay = Some(Replay::new(game_seed, &field, 1));
+        }
         event = PlayerEvent::Idle;
+        let mut inputs = InputQueue::new();
         ui = TerminalUI::new();
-        ui.init(width, height);
-        loop {
-            let result = play(&mut state, &field, &event, &random_range);
+        ui.init(&field);
+        let mut controller: Option<Box<dyn Controller>> = match bot_command {
+            Some(command) => {
+                let timeout = Duration::from_millis(bot_timeout);
+                match ExternalBot::spawn(command, timeout) {
+                    Ok(bot) => Some(Box::new(bot)),
+                    Err(err) => {
+                        errors.push(format!("Failed to start bot: {err}"));
+                        break;
+                    }
+                }
+            }
+            None => match &genome {
+                Some(genome) => Some(Box::new(NeuralBot::new(genome.clone()))),
+                None => bot_name.and_then(bot),
+            },
+        };
+        let outcome = loop {
+            // Ticks while paused change nothing and are left out
+            if let Some(replay) = &mut replay
+                && !(state.phase == Phase::Paused && event == PlayerEvent::Idle)
+            {
+                replay.record(&[event]);
+            }
+            let result = play(&mut state, &field, &[event], &mut rng);
             match result {
-                rattlesnake::GameResult::Continue => {}
-                rattlesnake::GameResult::GameOver => break,
+                GameResult::Continue => {}
+                GameResult::GameOver(outcome) => break outcome,
             }
 
-            ui.render(&state.snake, &state.food);
+            ui.render(&state);
 
-            event = ui.poll(250);
-            if let PlayerEvent::Quit = event {
-                break;
+            // Keys pressed during the tick are queued, to be taken one turn
+            // per tick
+            let keys = ui.poll(state.tick_millis(&field));
+            for key in &keys {
+                inputs.push(*key);
+            }
+            if state.phase == Phase::Paused {
+                // Presses while paused would turn the snake on resuming
+                inputs.clear();
             }
+            let control = keys.into_iter().find(|key| {
+                matches!(
+                    key,
+                    PlayerEvent::Quit
+                        | PlayerEvent::SaveAndQuit
+                        | PlayerEvent::Pause
+                )
+            });
+            event = match (control, &mut controller) {
+                (Some(control), _) => control,
+                // Moves are only taken while the game runs
+                (None, _) if state.phase != Phase::Running => PlayerEvent::Idle,
+                (None, Some(controller)) => {
+                    let event = controller.next_event(&state, &field, 0);
+                    if let Some(err) = controller.take_errors().pop() {
+                        ui.status(&format!("Bot: {err}"));
+                    }
+                    event
+                }
+                (None, None) => inputs.next(state.snakes[0].direction),
+            };
+        };
+        if let Some(path) = record
+            && let Some(replay) = &replay
+            && let Err(err) = fs::write(path, replay.to_json())
+        {
+            errors.push(format!("Failed to write replay {path}: {err}"));
         }
-        if let PlayerEvent::Quit = event {
+        if let PlayerEvent::SaveAndQuit = event {
+            let save = SavedGame::new(&field, &state, &rng);
+            if let Err(err) = write_file(save_path, &save.to_json()) {
+                errors.push(format!("Failed to save game {save_path}: {err}"));
+            }
+        }
+        if outcome == Outcome::Quit {
+            break;
+        }
+        if let PlayerEvent::Quit = ui.game_over(&outcome) {
             break;
         }
     }
 
     ui.deinit();
+    for err in errors {
+        eprintln!("{err}");
+    }
+}
+
+// Play back a recorded game with pause, single steps and fast-forward.
+fn watch_replay(path: &str) {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Failed to read replay {path}: {err}");
+        process::exit(1);
+    });
+    let replay = Replay::from_json(&text).unwrap_or_else(|err| {
+        eprintln!("Invalid replay {path}: {err}");
+        process::exit(1);
+    });
+    let total = replay.len();
+    let mut player = ReplayPlayer::new(replay);
+
+    let mut ui = TerminalUI::new();
+    ui.init(player.field());
+    let mut paused = false;
+    let mut fast_forward = false;
+    let mut outcome = None;
+    let mut deadline = Instant::now();
+    loop {
+        let tick = player.state().tick_millis(player.field());
+        let millis = if fast_forward {
+            tick / FAST_FORWARD
+        } else {
+            tick
+        };
+        if !paused && Instant::now() >= deadline {
+            deadline = Instant::now() + Duration::from_millis(millis);
+            step_replay(&mut player, &mut ui, &mut outcome);
+        }
+
+        let mode = if let Some(outcome) = outcome {
+            format!("end: {outcome}")
+        } else if player.is_finished() {
+            "end".to_string()
+        } else if paused {
+            "paused".to_string()
+        } else if fast_forward {
+            "fast-forward".to_string()
+        } else {
+            "playing".to_string()
+        };
+        ui.status(&format!(
+            "tick {}/{total} {mode}  [space] pause [n] step [f] fast [q] quit",
+            player.tick()
+        ));
+
+        let wait = if paused {
+            Duration::from_millis(TICK_MILLIS)
+        } else {
+            deadline.saturating_duration_since(Instant::now())
+        };
+        match ui.poll_playback(wait.as_millis() as u64) {
+            Playback::TogglePause => paused = !paused,
+            Playback::Step if paused => {
+                step_replay(&mut player, &mut ui, &mut outcome)
+            }
+            Playback::ToggleFastForward => fast_forward = !fast_forward,
+            Playback::Quit => break,
+            _ => {}
+        }
+    }
+
+    ui.deinit();
+}
+
+// Advance the replay by one tick and show the result.
+fn step_replay(
+    player: &mut ReplayPlayer,
+    ui: &mut TerminalUI,
+    outcome: &mut Option<Outcome>,
+) {
+    match player.step() {
+        Some(GameResult::Continue) => {
+            let state = player.state();
+            ui.render(state);
+        }
+        Some(GameResult::GameOver(end)) => *outcome = Some(end),
+        None => {}
+    }
+}
+
+// Load a saved game or exit with an error message.
+fn load_save(path: &str) -> SavedGame {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Failed to read save {path}: {err}");
+        process::exit(1);
+    });
+    SavedGame::from_json(&text).unwrap_or_else(|err| {
+        eprintln!("Invalid save {path}: {err}");
+        process::exit(1);
+    })
 }
@@ -0,0 +1,427 @@
+use apputils::{
+    enable_logging, load_level, option_value, option_values, parse_number,
+};
+use rating::{Elo, Glicko, PairResult};
+use rattlesnake::{
+    BOTS, Boundary, Controller, ExternalBot, Field, GameEvent, GameResult,
+    GameState, PlayerEvent, Random, SeededRng, bot, play,
+};
+use std::cmp::Ordering;
+use std::time::Duration;
+use std::{env, process, thread};
+
+mod rating;
+
+const LOG_DIR: &str = "var/log/";
+const LOG_FILE: &str = "snake-tournament.log";
+const ROUNDS: usize = 10;
+const SNAKES: usize = 2;
+const MAX_TICKS: u64 = 10_000;
+const BOT_TIMEOUT_MILLIS: u64 = 100;
+
+// Who plays for an entrant.
+enum Player {
+    Bot(String),
+    // Command of an external bot.
+    External(String),
+}
+
+struct Entrant {
+    name: String,
+    player: Player,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum System {
+    // Every group of entrants meets once per round.
+    RoundRobin,
+    // Entrants with similar points meet, avoiding rematches where possible.
+    Swiss,
+}
+
+struct Options {
+    entrants: Vec<Entrant>,
+    field: Field,
+    system: System,
+    rounds: usize,
+    // Snakes per game.
+    snakes: usize,
+    seed: u64,
+    max_ticks: u64,
+    bot_timeout: Duration,
+}
+
+// A game to play: its seed and the entrant playing each snake.
+struct Game {
+    seed: u64,
+    seats: Vec<usize>,
+}
+
+// How a game went for each snake, by seat.
+struct GameRecord {
+    seats: Vec<usize>,
+    // Tick each snake survived until, with the length of snakes still alive
+    // at the end; higher is better.
+    standings: Vec<(u64, usize)>,
+}
+
+impl GameRecord {
+    // Result of every pair of snakes, by entrant.
+    fn pairs(&self) -> Vec<PairResult> {
+        let mut pairs = Vec::new();
+        for i in 0..self.seats.len() {
+            for j in i + 1..self.seats.len() {
+                let score = match self.standings[i].cmp(&self.standings[j]) {
+                    Ordering::Greater => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Less => 0.0,
+                };
+                pairs.push(PairResult {
+                    a: self.seats[i],
+                    b: self.seats[j],
+                    score,
+                });
+            }
+        }
+        pairs
+    }
+
+    // Entrant that finished ahead of all others, if any.
+    fn winner(&self) -> Option<usize> {
+        let best = self.standings.iter().max()?;
+        let mut top =
+            (0..self.seats.len()).filter(|&seat| self.standings[seat] == *best);
+        match (top.next(), top.next()) {
+            (Some(seat), None) => Some(self.seats[seat]),
+            _ => None,
+        }
+    }
+}
+
+// Standing of an entrant over the whole tournament.
+#[derive(Default, Clone)]
+struct Standing {
+    games: usize,
+    wins: usize,
+    // Sum of the pairwise results.
+    points: f64,
+    // Entrants met so far.
+    opponents: Vec<usize>,
+}
+
+fn main() {
+    enable_logging(LOG_DIR, LOG_FILE);
+
+    let args: Vec<String> = env::args().collect();
+    let options = parse_options(&args);
+    let entrants = options.entrants.len();
+
+    let mut rng = SeededRng::new(options.seed);
+    let mut standings = vec![Standing::default(); entrants];
+    let mut elo = Elo::new(entrants);
+    let mut glicko = Glicko::new(entrants);
+    let mut games = 0;
+    for round in 0..options.rounds {
+        let schedule = match options.system {
+            System::RoundRobin => round_robin(&options, round, &mut rng),
+            System::Swiss => swiss(&options, &standings, &mut rng),
+        };
+        let records = play_all(&options, &schedule);
+
+        let mut period = Vec::new();
+        for record in &records {
+            let pairs = record.pairs();
+            elo.update(&pairs, record.seats.len());
+            for pair in &pairs {
+                standings[pair.a].points += pair.score;
+                standings[pair.b].points += 1.0 - pair.score;
+            }
+            for &seat in &record.seats {
+                let standing = &mut standings[seat];
+                standing.games += 1;
+                standing.opponents.extend(&record.seats);
+            }
+            if let Some(winner) = record.winner() {
+                standings[winner].wins += 1;
+            }
+            period.extend(pairs);
+        }
+        glicko.update(&period);
+        games += records.len();
+    }
+
+    let system = match options.system {
+        System::RoundRobin => "Round robin",
+        System::Swiss => "Swiss",
+    };
+    println!(
+        "{system}, {} rounds, {games} games of {} snakes, master seed {}",
+        options.rounds, options.snakes, options.seed
+    );
+    print_table(&options.entrants, &standings, &elo, &glicko);
+}
+
+// Every group of `snakes` entrants, with the seats turned each round so
+// that everyone gets to start from every position.
+fn round_robin(
+    options: &Options,
+    round: usize,
+    rng: &mut SeededRng,
+) -> Vec<Game> {
+    combinations(options.entrants.len(), options.snakes)
+        .into_iter()
+        .map(|mut seats| {
+            seats.rotate_left(round % options.snakes);
+            Game {
+                seed: rng.next_u64(),
+                seats,
+            }
+        })
+        .collect()
+}
+
+// All `k`-element subsets of `0..n`, in lexicographic order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut all = Vec::new();
+    let mut current = Vec::with_capacity(k);
+    fn extend(
+        start: usize,
+        n: usize,
+        k: usize,
+        current: &mut Vec<usize>,
+        all: &mut Vec<Vec<usize>>,
+    ) {
+        if current.len() == k {
+            all.push(current.clone());
+            return;
+        }
+        for i in start..n {
+            current.push(i);
+            extend(i + 1, n, k, current, all);
+            current.pop();
+        }
+    }
+    extend(0, n, k, &mut current, &mut all);
+    all
+}
+
+// Group entrants with similar points, shuffled first so that ties are
+// broken at random. Each group is filled with the best placed entrants that
+// have not met its members yet, or the best placed ones if all have.
+// Entrants left over when the groups are full sit out the round.
+fn swiss(
+    options: &Options,
+    standings: &[Standing],
+    rng: &mut SeededRng,
+) -> Vec<Game> {
+    let mut order: Vec<usize> = (0..standings.len()).collect();
+    for i in (1..order.len()).rev() {
+        order.swap(i, rng.index(i + 1));
+    }
+    order.sort_by(|a, b| standings[*b].points.total_cmp(&standings[*a].points));
+
+    let mut games = Vec::new();
+    while order.len() >= options.snakes {
+        let mut seats = vec![order.remove(0)];
+        while seats.len() < options.snakes {
+            let fresh = order.iter().position(|candidate| {
+                seats
+                    .iter()
+                    .all(|seat| !standings[*seat].opponents.contains(candidate))
+            });
+            seats.push(order.remove(fresh.unwrap_or(0)));
+        }
+        games.push(Game {
+            seed: rng.next_u64(),
+            seats,
+        });
+    }
+    games
+}
+
+// Play all games, spreading them over all cores.
+fn play_all(options: &Options, games: &[Game]) -> Vec<GameRecord> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = games.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = games
+            .chunks(chunk)
+            .map(|games| {
+                scope.spawn(move || {
+                    games
+                        .iter()
+                        .map(|game| play_game(options, game))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("game panicked"))
+            .collect()
+    })
+}
+
+// Play a game until it ends or reaches the tick limit.
+fn play_game(options: &Options, game: &Game) -> GameRecord {
+    let mut controllers: Vec<Box<dyn Controller>> = game
+        .seats
+        .iter()
+        .map(|&seat| match &options.entrants[seat].player {
+            Player::Bot(name) => bot(name).expect("bot was checked"),
+            Player::External(command) => {
+                match ExternalBot::spawn(command, options.bot_timeout) {
+                    Ok(bot) => Box::new(bot),
+                    Err(err) => {
+                        eprintln!("Failed to start bot {command}: {err}");
+                        process::exit(1);
+                    }
+                }
+            }
+        })
+        .collect();
+    let field = &options.field;
+    let mut state = GameState::with_players(game.seats.len());
+    let mut rng = SeededRng::new(game.seed);
+    let mut events = vec![PlayerEvent::Idle; game.seats.len()];
+    let mut died = vec![None; game.seats.len()];
+    let mut ticks = 0;
+    while ticks < options.max_ticks {
+        ticks += 1;
+        let result = play(&mut state, field, &events, &mut rng);
+        for event in state.events() {
+            if let GameEvent::Died { snake, .. } = event {
+                died[*snake] = Some(ticks);
+            }
+        }
+        if let GameResult::GameOver(_) = result {
+            break;
+        }
+        for (i, controller) in controllers.iter_mut().enumerate() {
+            events[i] = controller.next_event(&state, field, i);
+        }
+    }
+
+    let standings = state
+        .snakes
+        .iter()
+        .zip(died)
+        .map(|(snake, died)| match died {
+            Some(tick) => (tick, 0),
+            None => (ticks + 1, snake.body.len()),
+        })
+        .collect();
+    GameRecord {
+        seats: game.seats.clone(),
+        standings,
+    }
+}
+
+fn print_table(
+    entrants: &[Entrant],
+    standings: &[Standing],
+    elo: &Elo,
+    glicko: &Glicko,
+) {
+    let mut order: Vec<usize> = (0..entrants.len()).collect();
+    order.sort_by(|a, b| glicko.ratings[*b].total_cmp(&glicko.ratings[*a]));
+    let width = entrants.iter().map(|e| e.name.len()).max().unwrap_or(0);
+    println!(
+        "{:>4}  {:<width$}  {:>6}  {:>4}  {:>6}  {:>5}  {:>5}  {:>7}",
+        "Rank", "Bot", "Glicko", "RD", "Elo", "Games", "Wins", "Points"
+    );
+    for (rank, &i) in order.iter().enumerate() {
+        let standing = &standings[i];
+        println!(
+            "{:>4}  {:<width$}  {:>6.0}  {:>4.0}  {:>6.0}  {:>5}  {:>5}  {:>7.1}",
+            rank + 1,
+            entrants[i].name,
+            glicko.ratings[i],
+            glicko.deviations[i],
+            elo.ratings[i],
+            standing.games,
+            standing.wins,
+            standing.points
+        );
+    }
+}
+
+fn parse_options(args: &[String]) -> Options {
+    let mut entrants = Vec::new();
+    for name in option_values(args, "--bot") {
+        if bot(name).is_none() {
+            eprintln!("Unknown bot {name}, choose one of {}", BOTS.join(", "));
+            process::exit(1);
+        }
+        entrants.push(Entrant {
+            name: name.to_string(),
+            player: Player::Bot(name.to_string()),
+        });
+    }
+    for command in option_values(args, "--bot-cmd") {
+        entrants.push(Entrant {
+            name: command.to_string(),
+            player: Player::External(command.to_string()),
+        });
+    }
+    if entrants.is_empty() {
+        entrants = BOTS
+            .iter()
+            .map(|name| Entrant {
+                name: name.to_string(),
+                player: Player::Bot(name.to_string()),
+            })
+            .collect();
+    }
+
+    // Number entrants that share a name, e.g. the same bot entered twice
+    for i in 0..entrants.len() {
+        let copies = entrants[..i]
+            .iter()
+            .filter(|e| e.name.split(" #").next() == Some(&entrants[i].name))
+            .count();
+        if copies > 0 {
+            entrants[i].name = format!("{} #{}", entrants[i].name, copies + 1);
+        }
+    }
+
+    let system = match option_value(args, "--system") {
+        None | Some("round-robin") => System::RoundRobin,
+        Some("swiss") => System::Swiss,
+        Some(system) => {
+            eprintln!("Unknown system {system}, choose round-robin or swiss");
+            process::exit(1);
+        }
+    };
+    let boundary = if args.iter().any(|arg| arg == "--wrap") {
+        Boundary::Wrap
+    } else {
+        Boundary::Walls
+    };
+    let field = match option_value(args, "--level") {
+        Some(path) => load_level(path).with_boundary(boundary),
+        None => Field::new(20, 20).with_boundary(boundary),
+    };
+    let snakes = parse_number(args, "--snakes", SNAKES);
+    if snakes < 2 || snakes > entrants.len() {
+        eprintln!(
+            "Games need between 2 and {} snakes, one per entrant",
+            entrants.len()
+        );
+        process::exit(1);
+    }
+    Options {
+        entrants,
+        field,
+        system,
+        rounds: parse_number(args, "--rounds", ROUNDS),
+        snakes,
+        seed: parse_number(args, "--seed", 0),
+        max_ticks: parse_number(args, "--max-ticks", MAX_TICKS),
+        bot_timeout: Duration::from_millis(parse_number(
+            args,
+            "--bot-timeout",
+            BOT_TIMEOUT_MILLIS,
+        )),
+    }
+}
@@ -0,0 +1,119 @@
+//! Player ratings from pairwise results.
+//!
+//! A game between several snakes counts as one result for every pair of
+//! them: 1 for the better placed snake, 0 for the other and 0.5 each for a
+//! tie.
+use std::f64::consts::{LN_10, PI};
+
+const ELO_START: f64 = 1500.0;
+// Most an Elo rating moves after a game against a single opponent.
+const ELO_K: f64 = 32.0;
+const GLICKO_START: f64 = 1500.0;
+// Rating deviation of a new player, and the most it can grow to.
+const GLICKO_MAX_RD: f64 = 350.0;
+// Growth of the rating deviation per rating period without games.
+const GLICKO_C: f64 = 30.0;
+const Q: f64 = LN_10 / 400.0;
+
+// Result for `a` against `b`: 1 for a win, 0.5 for a tie and 0 for a loss.
+pub struct PairResult {
+    pub a: usize,
+    pub b: usize,
+    pub score: f64,
+}
+
+// Expected score of a player rated `rating` against `opponent`.
+fn expected(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+// Elo ratings, updated after every game.
+pub struct Elo {
+    pub ratings: Vec<f64>,
+}
+
+impl Elo {
+    pub fn new(players: usize) -> Self {
+        Elo {
+            ratings: vec![ELO_START; players],
+        }
+    }
+
+    // Update with the pairwise results of one game of `size` snakes. Every
+    // player meets `size - 1` opponents, so each result moves the ratings by
+    // a share of `ELO_K`.
+    pub fn update(&mut self, results: &[PairResult], size: usize) {
+        let k = ELO_K / (size.max(2) - 1) as f64;
+        let mut deltas = vec![0.0; self.ratings.len()];
+        for result in results {
+            let (ra, rb) = (self.ratings[result.a], self.ratings[result.b]);
+            let delta = k * (result.score - expected(ra, rb));
+            deltas[result.a] += delta;
+            deltas[result.b] -= delta;
+        }
+        for (rating, delta) in self.ratings.iter_mut().zip(deltas) {
+            *rating += delta;
+        }
+    }
+}
+
+// Glicko ratings, see http://www.glicko.net/glicko/glicko.pdf. Results are
+// collected into rating periods, here the rounds of a tournament.
+pub struct Glicko {
+    pub ratings: Vec<f64>,
+    pub deviations: Vec<f64>,
+}
+
+impl Glicko {
+    pub fn new(players: usize) -> Self {
+        Glicko {
+            ratings: vec![GLICKO_START; players],
+            deviations: vec![GLICKO_MAX_RD; players],
+        }
+    }
+
+    // Update with the pairwise results of a rating period.
+    pub fn update(&mut self, results: &[PairResult]) {
+        let players = self.ratings.len();
+        let deviations: Vec<f64> = self
+            .deviations
+            .iter()
+            .map(|rd| (rd * rd + GLICKO_C * GLICKO_C).sqrt().min(GLICKO_MAX_RD))
+            .collect();
+
+        // Sums over each player's games of g²E(1 - E) and g(s - E)
+        let mut variance = vec![0.0; players];
+        let mut improvement = vec![0.0; players];
+        let mut add = |player: usize, opponent: usize, score: f64| {
+            let g = g(deviations[opponent]);
+            let e = 1.0
+                / (1.0
+                    + 10f64.powf(
+                        -g * (self.ratings[player] - self.ratings[opponent])
+                            / 400.0,
+                    ));
+            variance[player] += g * g * e * (1.0 - e);
+            improvement[player] += g * (score - e);
+        };
+        for result in results {
+            add(result.a, result.b, result.score);
+            add(result.b, result.a, 1.0 - result.score);
+        }
+
+        for i in 0..players {
+            if variance[i] == 0.0 {
+                self.deviations[i] = deviations[i];
+                continue;
+            }
+            let d2 = 1.0 / (Q * Q * variance[i]);
+            let precision = 1.0 / (deviations[i] * deviations[i]) + 1.0 / d2;
+            self.ratings[i] += Q / precision * improvement[i];
+            self.deviations[i] = (1.0 / precision).sqrt();
+        }
+    }
+}
+
+// Weight of a result against an opponent with rating deviation `rd`.
+fn g(rd: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * Q * Q * rd * rd / (PI * PI)).sqrt()
+}
@@ -0,0 +1,15 @@
+// Tells the game when its tab is hidden or loses focus, so that it pauses;
+// see `snake_wasm::looked_away`. Load it next to `gl.js`.
+miniquad_add_plugin({
+    name: "snake_visibility",
+    version: 1,
+    on_init: function () {
+        var update = function () {
+            var away = document.hidden || !document.hasFocus();
+            wasm_exports.snake_visibility_changed(away);
+        };
+        document.addEventListener("visibilitychange", update);
+        window.addEventListener("blur", update);
+        window.addEventListener("focus", update);
+    },
+});
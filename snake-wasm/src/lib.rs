@@ -1,24 +1,142 @@
+use log::error;
 use macroquad::{color, input, math, shapes, text, time, window};
-use rattlesnake::PlayerEvent;
+use rattlesnake::{
+    Boundary, Effect, Field, Food, FoodRules, Outcome, Phase, PlayerEvent,
+    SavedGame, Snake,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const SAVE_KEY: &str = "rattlesnake-save";
+const BOUNDARY_KEY: &str = "rattlesnake-boundary";
+const FOOD_KEY: &str = "rattlesnake-food";
+// Food rules to choose from, by name, see `FoodRules`.
+const FOOD_PRESETS: [(&str, &str); 4] = [
+    ("normal", "normal=1"),
+    ("mixed", "normal=10,bonus=2,shrink=1,speed-up=1,slow-down=1"),
+    ("power-ups", "normal=10,ghost=1,magnet=1,slow-motion=1"),
+    (
+        "everything",
+        "normal=10,bonus=2,shrink=1,speed-up=1,slow-down=1,ghost=1,magnet=1,\
+         slow-motion=1",
+    ),
+];
+const SNAKE_COLORS: [color::Color; 4] =
+    [color::GREEN, color::ORANGE, color::SKYBLUE, color::PINK];
+// Food by kind, in the order of `FoodKind::ALL`.
+const FOOD_COLORS: [color::Color; 8] = [
+    color::WHITE,
+    color::GOLD,
+    color::RED,
+    color::VIOLET,
+    color::BLUE,
+    color::LIGHTGRAY,
+    color::MAGENTA,
+    color::LIME,
+];
 
 pub struct BrowserUI {
     field_x: u16,
     field_y: u16,
     field_width: u16,
     field_height: u16,
+    boundary: Boundary,
+    walls: Vec<(u16, u16)>,
     thickness: u16,
     base_thickness: u16,
     score: Score,
+    // Name of the bot playing, if any.
+    bot: Option<&'static str>,
     arrow_keys: ArrowKeys,
     touch_toggle: ToggleButton,
 }
 
+// Choices on the menu shown before each new game.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum MenuInput {
+    Play,
+    Quit,
+    ToggleBoundary,
+    NextFood,
+}
+
+// Settings of new games, kept in the browser's local storage.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Settings {
+    pub boundary: Boundary,
+    // Index into `FOOD_PRESETS`.
+    food: usize,
+}
+
+impl Settings {
+    // The stored settings, or the defaults for those never stored.
+    pub fn load() -> Self {
+        let storage = &mut quad_storage::STORAGE.lock().unwrap();
+        let boundary = match storage.get(BOUNDARY_KEY).as_deref() {
+            Some("wrap") => Boundary::Wrap,
+            _ => Boundary::Walls,
+        };
+        let food = storage.get(FOOD_KEY).and_then(|name| {
+            FOOD_PRESETS.iter().position(|(preset, _)| *preset == name)
+        });
+        Settings {
+            boundary,
+            food: food.unwrap_or(0),
+        }
+    }
+
+    pub fn store(&self) {
+        let storage = &mut quad_storage::STORAGE.lock().unwrap();
+        let boundary = match self.boundary {
+            Boundary::Walls => "walls",
+            Boundary::Wrap => "wrap",
+        };
+        storage.set(BOUNDARY_KEY, boundary);
+        storage.set(FOOD_KEY, self.food_name());
+    }
+
+    // A field of the given size played with these settings.
+    pub fn field(&self, width: u16, height: u16) -> Field {
+        let rules: FoodRules =
+            FOOD_PRESETS[self.food].1.parse().expect("preset is valid");
+        Field::new(width, height)
+            .with_boundary(self.boundary)
+            .with_food_rules(rules)
+    }
+
+    // Name of the food preset.
+    pub fn food_name(&self) -> &'static str {
+        FOOD_PRESETS[self.food].0
+    }
+
+    // Switch to the next food preset, after the last back to the first.
+    pub fn next_food(&mut self) {
+        self.food = (self.food + 1) % FOOD_PRESETS.len();
+    }
+}
+
 pub struct ArrowKeys {
     left: RectButton,
     right: RectButton,
     up: RectButton,
     down: RectButton,
+    // Buttons turning relative to the snake's direction.
+    turn_left: RectButton,
+    turn_right: RectButton,
     is_visible: bool,
+    // Whether the turn buttons are shown instead of the arrows.
+    relative: bool,
+}
+
+impl ArrowKeys {
+    fn visible(&self) -> Vec<&RectButton> {
+        if !self.is_visible {
+            Vec::new()
+        } else if self.relative {
+            vec![&self.turn_left, &self.turn_right]
+        } else {
+            vec![&self.up, &self.down, &self.left, &self.right]
+        }
+    }
 }
 
 pub struct ToggleButton {
@@ -39,27 +157,30 @@ struct Text {
 
 struct Score {
     text: Text,
-    value: u8,
+    values: Vec<u8>,
 }
 
 impl BrowserUI {
-    pub fn new(width: u16, height: u16, thickness: u16) -> Self {
+    pub fn new(field: &Field, thickness: u16) -> Self {
         let score = Score {
             text: Text {
                 content: "Score: ",
                 font_size: 40,
             },
-            value: 0,
+            values: Vec::new(),
         };
 
         BrowserUI {
             field_x: 0,
             field_y: 0,
-            field_width: width,
-            field_height: height,
+            field_width: field.width(),
+            field_height: field.height(),
+            boundary: field.boundary(),
+            walls: field.walls().cloned().collect(),
             thickness,
             base_thickness: thickness,
             score,
+            bot: None,
             arrow_keys: create_arrow_keys(),
             touch_toggle: create_touch_toggle(100.0),
         }
@@ -111,12 +232,18 @@ impl BrowserUI {
         self.arrow_keys.left.rect.y = second_row;
         self.arrow_keys.right.rect.x = right_column;
         self.arrow_keys.right.rect.y = second_row;
+        self.arrow_keys.turn_left.rect.x = left_column;
+        self.arrow_keys.turn_left.rect.y = second_row;
+        self.arrow_keys.turn_right.rect.x = right_column;
+        self.arrow_keys.turn_right.rect.y = second_row;
 
         for button in [
             &mut self.arrow_keys.up,
             &mut self.arrow_keys.down,
             &mut self.arrow_keys.left,
             &mut self.arrow_keys.right,
+            &mut self.arrow_keys.turn_left,
+            &mut self.arrow_keys.turn_right,
         ] {
             button.rect.w = size;
             button.rect.h = size;
@@ -134,12 +261,7 @@ impl BrowserUI {
                 - self.touch_toggle.button.rect.h;
     }
 
-    pub fn render(
-        &mut self,
-        snake: &[(u16, u16)],
-        food: &Vec<(u16, u16)>,
-        score: u8,
-    ) {
+    pub fn render(&mut self, snakes: &[Snake], food: &[Food]) {
         self.update_positions();
 
         self.draw_field(
@@ -157,17 +279,36 @@ impl BrowserUI {
             self.draw_food(&(self.field_x, self.field_y), f, self.thickness);
         }
 
-        for s in snake {
-            self.draw_snake(&(self.field_x, self.field_y), s, self.thickness);
+        for (i, snake) in snakes.iter().enumerate() {
+            if !snake.alive {
+                continue;
+            }
+            let color = SNAKE_COLORS[i % SNAKE_COLORS.len()];
+            for s in &snake.body {
+                self.draw_snake(
+                    &(self.field_x, self.field_y),
+                    s,
+                    self.thickness,
+                    color,
+                );
+            }
         }
 
-        self.score.value = score;
+        self.score.values = snakes.iter().map(|snake| snake.score).collect();
         self.draw_score(
             self.field_x,
             self.field_y.saturating_sub(self.thickness),
         );
     }
 
+    pub fn set_bot(&mut self, bot: Option<&'static str>) {
+        self.bot = bot;
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary;
+    }
+
     fn draw_field(
         &self,
         x: u16,
@@ -176,14 +317,27 @@ impl BrowserUI {
         height: u16,
         thickness: u16,
     ) {
+        let border_color = match self.boundary {
+            Boundary::Walls => color::WHITE,
+            Boundary::Wrap => color::DARKGRAY,
+        };
         shapes::draw_rectangle_lines(
             x as f32,
             y as f32,
             (width * thickness + 2 * thickness) as f32,
             (height * thickness + 2 * thickness) as f32,
             thickness as f32,
-            macroquad::color::WHITE,
+            border_color,
         );
+        for pos in &self.walls {
+            shapes::draw_rectangle(
+                (x + pos.0 * thickness) as f32,
+                (y + pos.1 * thickness) as f32,
+                thickness as f32,
+                thickness as f32,
+                color::GRAY,
+            );
+        }
     }
 
     fn draw_snake(
@@ -191,29 +345,35 @@ impl BrowserUI {
         anchor: &(u16, u16),
         pos: &(u16, u16),
         thickness: u16,
+        color: color::Color,
     ) {
         shapes::draw_rectangle(
             (anchor.0 + pos.0 * thickness) as f32,
             (anchor.1 + pos.1 * thickness) as f32,
             thickness as f32,
             thickness as f32,
-            macroquad::color::GREEN,
+            color,
         );
     }
 
-    fn draw_food(&self, anchor: &(u16, u16), pos: &(u16, u16), thickness: u16) {
+    fn draw_food(&self, anchor: &(u16, u16), food: &Food, thickness: u16) {
         shapes::draw_rectangle(
-            (anchor.0 + pos.0 * thickness) as f32,
-            (anchor.1 + pos.1 * thickness) as f32,
+            (anchor.0 + food.pos.0 * thickness) as f32,
+            (anchor.1 + food.pos.1 * thickness) as f32,
             thickness as f32,
             thickness as f32,
-            macroquad::color::WHITE,
+            FOOD_COLORS[food.kind as usize],
         );
     }
 
     fn draw_score(&self, x: u16, y: u16) {
-        let score_text =
-            format!("{}{}", self.score.text.content, self.score.value);
+        let values: Vec<String> =
+            self.score.values.iter().map(|v| v.to_string()).collect();
+        let mut score_text =
+            format!("{}{}", self.score.text.content, values.join(" - "));
+        if let Some(bot) = self.bot {
+            score_text.push_str(&format!(" ({bot})"));
+        }
         text::draw_text(
             &score_text,
             x as f32,
@@ -224,17 +384,7 @@ impl BrowserUI {
     }
 
     fn draw_arrow_keys(&mut self) {
-        if !self.arrow_keys.is_visible {
-            return;
-        }
-        let arrow_keys = [
-            &self.arrow_keys.up,
-            &self.arrow_keys.down,
-            &self.arrow_keys.left,
-            &self.arrow_keys.right,
-        ];
-
-        for button in &arrow_keys {
+        for button in self.arrow_keys.visible() {
             let rect = &button.rect;
             shapes::draw_rectangle(
                 rect.x,
@@ -285,60 +435,167 @@ impl BrowserUI {
         );
     }
 
-    pub fn poll(&mut self, millis: u64, prev: &PlayerEvent) -> PlayerEvent {
-        let start = now_millis();
-        let mut event = PlayerEvent::Idle;
-        loop {
-            // Handle keyboard input
-            if self.is_pressed(macroquad::prelude::KeyCode::Up) {
-                event = PlayerEvent::Up;
-            }
-            if self.is_pressed(macroquad::prelude::KeyCode::Down) {
-                event = PlayerEvent::Down;
-            }
-            if self.is_pressed(macroquad::prelude::KeyCode::Left) {
-                event = PlayerEvent::Left;
+    // Events of the keys pressed and buttons tapped since the last frame.
+    pub fn poll(&mut self) -> Vec<PlayerEvent> {
+        use macroquad::prelude::KeyCode;
+        const KEYS: [(KeyCode, PlayerEvent); 10] = [
+            (KeyCode::Up, PlayerEvent::Up),
+            (KeyCode::Down, PlayerEvent::Down),
+            (KeyCode::Left, PlayerEvent::Left),
+            (KeyCode::Right, PlayerEvent::Right),
+            (KeyCode::Z, PlayerEvent::TurnLeft),
+            (KeyCode::X, PlayerEvent::TurnRight),
+            (KeyCode::P, PlayerEvent::Pause),
+            (KeyCode::Escape, PlayerEvent::Quit),
+            (KeyCode::S, PlayerEvent::SaveAndQuit),
+            (KeyCode::B, PlayerEvent::NextBot),
+        ];
+        let mut events: Vec<PlayerEvent> = KEYS
+            .iter()
+            .filter(|(key, _)| self.is_pressed(*key))
+            .map(|(_, event)| *event)
+            .collect();
+        // Handle mouse/touch input
+        if input::is_mouse_button_pressed(input::MouseButton::Left) {
+            let pos = input::mouse_position();
+            let touch = math::Vec2::new(pos.0, pos.1);
+            if let Some(button) = self
+                .arrow_keys
+                .visible()
+                .into_iter()
+                .find(|button| button.rect.contains(touch))
+            {
+                events.push(button.event);
             }
-            if self.is_pressed(macroquad::prelude::KeyCode::Right) {
-                event = PlayerEvent::Right;
+            if self.touch_toggle.button.rect.contains(touch) {
+                // Cycle through arrows, turn buttons and no buttons
+                let keys = &mut self.arrow_keys;
+                (keys.is_visible, keys.relative) =
+                    match (keys.is_visible, keys.relative) {
+                        (false, _) => (true, false),
+                        (true, false) => (true, true),
+                        (true, true) => (false, false),
+                    };
+                self.touch_toggle.is_on = keys.is_visible;
+                self.touch_toggle.button.text.content =
+                    if keys.relative { "Turn" } else { "Touch" };
+                events.push(PlayerEvent::ToggleArrowKeys);
             }
-            if self.is_pressed(macroquad::prelude::KeyCode::Escape) {
-                event = PlayerEvent::Quit;
+            // Tapping the field pauses and resumes
+            let field = math::Rect::new(
+                self.field_x as f32,
+                self.field_y as f32,
+                ((self.field_width + 2) * self.thickness) as f32,
+                ((self.field_height + 2) * self.thickness) as f32,
+            );
+            if field.contains(touch) {
+                events.push(PlayerEvent::Pause);
             }
-            // Handle mouse/touch input
-            if input::is_mouse_button_pressed(input::MouseButton::Left) {
-                let pos = input::mouse_position();
-                let touch = math::Vec2::new(pos.0, pos.1);
-                if self.arrow_keys.is_visible {
-                    let arrow_keys = [
-                        &self.arrow_keys.up,
-                        &self.arrow_keys.down,
-                        &self.arrow_keys.left,
-                        &self.arrow_keys.right,
-                    ];
-                    for button in arrow_keys {
-                        if button.rect.contains(touch) {
-                            event = button.event;
-                            break;
-                        }
-                    }
-                }
-                if self.touch_toggle.button.rect.contains(touch) {
-                    self.touch_toggle.is_on = !self.touch_toggle.is_on;
-                    self.arrow_keys.is_visible = self.touch_toggle.is_on;
-                    self.draw_touch_toggle();
-                    self.draw_arrow_keys();
-                    event = PlayerEvent::ToggleArrowKeys;
-                    break;
-                }
+        }
+        events
+    }
+
+    // Draw the menu on top of the field: how the last game ended, if one
+    // did, and the settings of the next.
+    pub fn draw_menu(&self, outcome: Option<&Outcome>, settings: &Settings) {
+        let mut lines = match outcome {
+            Some(outcome) => {
+                vec!["Game over!".to_string(), format!("{outcome}.")]
             }
-            let is_timeout_reached = now_millis() - start >= millis;
-            let is_new_event = event != PlayerEvent::Idle && event != *prev;
-            if is_timeout_reached || is_new_event {
-                break;
+            None => vec!["Snake".to_string()],
+        };
+        let boundary = match settings.boundary {
+            Boundary::Walls => "walls",
+            Boundary::Wrap => "wrap around",
+        };
+        lines.push(format!("Edges: {boundary}, press w to change"));
+        let food = settings.food_name();
+        lines.push(format!("Food: {food}, press f to change"));
+        lines.push("Press any other key or tap to play".to_string());
+        self.draw_message(&lines);
+    }
+
+    // Draw the power-ups in effect and their ticks left below the field, in
+    // the colour of the snake that has them.
+    pub fn draw_effects(&self, effects: &[Effect]) {
+        let font_size = self.score.text.font_size;
+        let mut x = self.field_x as f32;
+        let y = (self.field_y + (self.field_height + 2) * self.thickness)
+            as f32
+            + font_size as f32;
+        for effect in effects {
+            let label = format!("{} {}  ", effect.power.name(), effect.ticks);
+            text::draw_text(
+                &label,
+                x,
+                y,
+                font_size as f32,
+                SNAKE_COLORS[effect.snake % SNAKE_COLORS.len()],
+            );
+            x += text::measure_text(&label, None, font_size, 1.0).width;
+        }
+    }
+
+    // Draw whether the game is paused or counting down on top of the field.
+    pub fn draw_phase(&self, phase: Phase) {
+        match phase {
+            Phase::Running => {}
+            Phase::Paused => self.draw_message(&[
+                "Paused".to_string(),
+                "Press p or tap the field to resume".to_string(),
+            ]),
+            Phase::Countdown(remaining) => {
+                self.draw_message(&[remaining.to_string()])
             }
         }
-        event
+    }
+
+    // Draw `lines` centered on the field.
+    fn draw_message(&self, lines: &[String]) {
+        let center_x = self.field_x as f32
+            + ((self.field_width + 2) * self.thickness) as f32 / 2.0;
+        let center_y = self.field_y as f32
+            + ((self.field_height + 2) * self.thickness) as f32 / 2.0;
+        let middle = (lines.len() as f32 - 1.0) / 2.0;
+        let font_size = self.thickness.max(10);
+        for (i, line) in lines.iter().enumerate() {
+            let dim = text::measure_text(line, None, font_size, 1.0);
+            let y = center_y + (i as f32 - middle) * dim.height * 1.5;
+            shapes::draw_rectangle(
+                center_x - dim.width / 2.0,
+                y - dim.height,
+                dim.width,
+                dim.height * 1.5,
+                color::BLACK,
+            );
+            text::draw_text(
+                line,
+                center_x - dim.width / 2.0,
+                y,
+                font_size as f32,
+                color::WHITE,
+            );
+        }
+    }
+
+    // Check for input on the menu; escape quits.
+    pub fn poll_menu(&self) -> Option<MenuInput> {
+        use macroquad::prelude::KeyCode;
+        if self.is_pressed(KeyCode::Escape) {
+            return Some(MenuInput::Quit);
+        }
+        if self.is_pressed(KeyCode::W) {
+            return Some(MenuInput::ToggleBoundary);
+        }
+        if self.is_pressed(KeyCode::F) {
+            return Some(MenuInput::NextFood);
+        }
+        if input::get_last_key_pressed().is_some()
+            || input::is_mouse_button_pressed(input::MouseButton::Left)
+        {
+            return Some(MenuInput::Play);
+        }
+        None
     }
 
     fn is_pressed(&self, key: macroquad::prelude::KeyCode) -> bool {
@@ -390,12 +647,32 @@ fn create_arrow_keys() -> ArrowKeys {
         event: PlayerEvent::Down,
     };
 
+    let turn_left = RectButton {
+        rect: math::Rect::new(0.0, 0.0, arrow_key_size, arrow_key_size),
+        text: Text {
+            content: "L",
+            font_size: 60,
+        },
+        event: PlayerEvent::TurnLeft,
+    };
+    let turn_right = RectButton {
+        rect: math::Rect::new(0.0, 0.0, arrow_key_size, arrow_key_size),
+        text: Text {
+            content: "R",
+            font_size: 60,
+        },
+        event: PlayerEvent::TurnRight,
+    };
+
     ArrowKeys {
         left,
         right,
         up,
         down,
+        turn_left,
+        turn_right,
         is_visible: false,
+        relative: false,
     }
 }
 
@@ -436,6 +713,48 @@ fn draw_text_centered(
     );
 }
 
+// Whether the tab was hidden or lost focus, set by the `snake_visibility`
+// plugin in `js/snake_visibility.js`.
+static LOOKED_AWAY: AtomicBool = AtomicBool::new(false);
+
+// Called by the `snake_visibility` plugin whenever the tab is hidden or
+// shown, or loses or gains focus.
+#[unsafe(no_mangle)]
+pub extern "C" fn snake_visibility_changed(away: bool) {
+    if away {
+        LOOKED_AWAY.store(true, Ordering::Relaxed);
+    }
+}
+
+// Version of the `snake_visibility` plugin this crate expects.
+#[unsafe(no_mangle)]
+pub extern "C" fn snake_visibility_crate_version() -> u32 {
+    1
+}
+
+// Whether the tab was hidden or lost focus since the last call. Browsers
+// stop drawing hidden tabs, so the game may only notice once the tab is
+// shown again.
+pub fn looked_away() -> bool {
+    LOOKED_AWAY.swap(false, Ordering::Relaxed)
+}
+
 pub fn now_millis() -> u64 {
     (time::get_time() * 1000.0) as u64
 }
+
+// Take the saved game out of the browser's local storage.
+pub fn take_saved_game() -> Option<SavedGame> {
+    let storage = &mut quad_storage::STORAGE.lock().unwrap();
+    let text = storage.get(SAVE_KEY)?;
+    storage.remove(SAVE_KEY);
+    SavedGame::from_json(&text)
+        .inspect_err(|err| error!("Discarding saved game: {err}"))
+        .ok()
+}
+
+// Keep a saved game in the browser's local storage.
+pub fn store_saved_game(save: &SavedGame) {
+    let storage = &mut quad_storage::STORAGE.lock().unwrap();
+    storage.set(SAVE_KEY, &save.to_json());
+}
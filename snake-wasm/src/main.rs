@@ -1,8 +1,13 @@
 use apputils::enable_logging;
-use macroquad::rand;
-use rattlesnake::RandomRange;
-use rattlesnake::{Field, GameState, PlayerEvent, play};
-use snake_wasm::{BrowserUI, now_millis};
+use macroquad::miniquad::date;
+use rattlesnake::{
+    BOTS, Boundary, Controller, GameResult, GameState, InputQueue, Outcome,
+    Phase, PlayerEvent, SavedGame, SeededRng, bot, play,
+};
+use snake_wasm::{
+    BrowserUI, MenuInput, Settings, looked_away, now_millis, store_saved_game,
+    take_saved_game,
+};
 
 const LOG_DIR: &str = "var/log/";
 const LOG_FILE: &str = "snake_wasm.log";
@@ -14,53 +19,145 @@ async fn main() {
     let width: u16 = 19;
     let height: u16 = 19;
     let thickness: u16 = 20;
-    let field = Field::new(width, height);
+    let mut settings = Settings::load();
+    let mut resumed = take_saved_game();
+    let mut field = match &resumed {
+        Some(save) => save.field.clone(),
+        None => settings.field(width, height),
+    };
 
-    let mut ui: BrowserUI;
+    let mut ui = BrowserUI::new(&field, thickness);
+    if resumed.is_none() {
+        if !menu(&mut ui, &GameState::new(), None, &mut settings).await {
+            return;
+        }
+        field = settings.field(width, height);
+    }
     let mut state: GameState;
     let mut event: PlayerEvent;
     let mut start: u64;
-    let random_range: RandomRange = rand::gen_range;
+    let mut rng = SeededRng::new((date::now() * 1000.0) as u64);
+    // Index into `BOTS` of the bot playing, if any
+    let mut bot_index: Option<usize> = None;
+    let mut controller: Option<Box<dyn Controller>> = None;
     loop {
-        ui = BrowserUI::new(width, height, thickness);
-        state = GameState::new();
-        event = PlayerEvent::Idle;
-        loop {
+        ui = BrowserUI::new(&field, thickness);
+        ui.set_bot(bot_index.map(|i| BOTS[i]));
+        match resumed.take() {
+            Some(save) => {
+                state = save.state;
+                rng = save.rng;
+            }
+            None => state = GameState::new(),
+        }
+        let mut inputs = InputQueue::new();
+        // Switching tabs in the menu must not pause the new game
+        looked_away();
+        let outcome = loop {
+            // Gather input over a tick, drawing every frame. Presses are
+            // queued and taken one turn per tick
             start = now_millis();
-            event = poll(&mut ui, &event).await;
-            if let PlayerEvent::Quit = event {
-                break;
+            let millis = state.tick_millis(&field);
+            let mut control = None;
+            while control.is_none() && now_millis() - start < millis {
+                for input in ui.poll() {
+                    match input {
+                        PlayerEvent::Quit
+                        | PlayerEvent::SaveAndQuit
+                        | PlayerEvent::Pause => {
+                            control = Some(input);
+                        }
+                        PlayerEvent::NextBot => {
+                            bot_index = match bot_index {
+                                None => Some(0),
+                                Some(i) if i + 1 < BOTS.len() => Some(i + 1),
+                                Some(_) => None,
+                            };
+                            controller = bot_index.and_then(|i| bot(BOTS[i]));
+                            ui.set_bot(bot_index.map(|i| BOTS[i]));
+                            inputs.clear();
+                        }
+                        _ => {
+                            inputs.push(input);
+                        }
+                    }
+                }
+                ui.render(&state.snakes, &state.food);
+                ui.draw_effects(&state.effects);
+                ui.draw_phase(state.phase);
+                ui.flush().await;
+                // Pause when the player switched tabs or windows
+                if looked_away()
+                    && state.phase == Phase::Running
+                    && control.is_none()
+                {
+                    control = Some(PlayerEvent::Pause);
+                }
             }
+            if state.phase == Phase::Paused {
+                // Presses while paused would turn the snake on resuming
+                inputs.clear();
+            }
+            event = match (control, &mut controller) {
+                (Some(control), _) => control,
+                // Moves are only taken while the game runs
+                (None, _) if state.phase != Phase::Running => PlayerEvent::Idle,
+                (None, Some(controller)) => {
+                    controller.next_event(&state, &field, 0)
+                }
+                (None, None) => inputs.next(state.snakes[0].direction),
+            };
 
-            while now_millis() - start < 250 {}
-
-            let result = play(&mut state, &field, &event, &random_range);
+            let result = play(&mut state, &field, &[event], &mut rng);
             match result {
-                rattlesnake::GameResult::Continue => {}
-                rattlesnake::GameResult::GameOver => break,
+                GameResult::Continue => {}
+                GameResult::GameOver(outcome) => break outcome,
             }
-
-            ui.render(&state.snake, &state.food, state.score);
-
-            ui.flush().await;
+        };
+        if let PlayerEvent::SaveAndQuit = event {
+            store_saved_game(&SavedGame::new(&field, &state, &rng));
         }
-        if let PlayerEvent::Quit = event {
+        if outcome == Outcome::Quit {
             break;
         }
-    }
-}
 
-async fn poll(ui: &mut BrowserUI, event: &PlayerEvent) -> PlayerEvent {
-    let start = now_millis();
-    let timeout_ms = 250;
-    let mut event_ = ui.poll(timeout_ms, event);
-    while let PlayerEvent::ToggleArrowKeys = event {
-        let remaining_ms = timeout_ms.saturating_sub(now_millis() - start);
-        if remaining_ms == 0 {
+        // Show how the game ended until the player continues
+        if !menu(&mut ui, &state, Some(outcome), &mut settings).await {
             break;
         }
-        event_ = ui.poll(remaining_ms, &event_);
+        field = settings.field(width, height);
+    }
+}
+
+// Show the menu over `state` until the player starts the next game, and
+// store the settings changed on the way. Returns false if the player quits
+// instead.
+async fn menu(
+    ui: &mut BrowserUI,
+    state: &GameState,
+    outcome: Option<Outcome>,
+    settings: &mut Settings,
+) -> bool {
+    loop {
+        ui.render(&state.snakes, &state.food);
+        ui.draw_menu(outcome.as_ref(), settings);
         ui.flush().await;
+        match ui.poll_menu() {
+            Some(MenuInput::Play) => return true,
+            Some(MenuInput::Quit) => return false,
+            Some(MenuInput::ToggleBoundary) => {
+                settings.boundary = match settings.boundary {
+                    Boundary::Walls => Boundary::Wrap,
+                    Boundary::Wrap => Boundary::Walls,
+                };
+                settings.store();
+                ui.set_boundary(settings.boundary);
+            }
+            Some(MenuInput::NextFood) => {
+                settings.next_food();
+                settings.store();
+            }
+            None => {}
+        }
     }
-    event_
 }